@@ -0,0 +1,50 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How the delay between the attempts of a failed task grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backoff {
+  /// Wait the base delay before every retry.
+  Fixed,
+  /// Double the delay after every attempt.
+  #[default]
+  Exponential,
+  /// Wait a random delay between zero and the exponential one, so that retries of concurrent tasks spread out.
+  Jittered,
+}
+
+impl Backoff {
+  /// Delay before the attempt following attempt number `attempt`, which starts at 1.
+  pub fn delay(self, base: Duration, attempt: usize) -> Duration {
+    let exponential = base.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+    match self {
+      Backoff::Fixed => base,
+      Backoff::Exponential => exponential,
+      Backoff::Jittered => exponential.mul_f64(fastrand::f64()),
+    }
+  }
+}
+
+impl FromStr for Backoff {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "fixed" => Ok(Backoff::Fixed),
+      "exponential" => Ok(Backoff::Exponential),
+      "jitter" | "jittered" => Ok(Backoff::Jittered),
+      other => Err(format!("unknown backoff `{other}`, expected fixed, exponential or jitter")),
+    }
+  }
+}
+
+impl fmt::Display for Backoff {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Backoff::Fixed => write!(f, "fixed"),
+      Backoff::Exponential => write!(f, "exponential"),
+      Backoff::Jittered => write!(f, "jitter"),
+    }
+  }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::backoff::Backoff;
+use crate::criteria::SuccessCriteria;
+use crate::profile::LoadProfile;
+use crate::rate::{Arrivals, Rate, Saturation};
+use crate::threshold::{FailureThresholds, StopMode};
+
+/// Settings of a pool run.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+  /// Command template, program first. See [`crate::template`] for the placeholders.
+  pub command: Vec<String>,
+  /// Per-task inputs, task `n` gets `inputs[n - 1]`.
+  pub inputs: Option<Vec<String>>,
+  /// Number of tasks to run, unbounded if `None`.
+  pub total_tasks: Option<usize>,
+  /// Ids of the tasks not to run, e.g. those that succeeded before an earlier run was interrupted.
+  ///
+  /// The other tasks keep their id and input, the summary only counts the tasks that run.
+  pub skip_tasks: HashSet<usize>,
+  /// Stop launching tasks once the run has lasted this long, then let the running ones finish.
+  pub duration: Option<Duration>,
+  /// Maximum number of tasks running at once, nothing is launched while it is 0.
+  pub concurrency: usize,
+  /// Vary the concurrency over time instead, `concurrency` is then ignored and the run ends with the profile.
+  pub profile: Option<LoadProfile>,
+  /// Launch tasks at this rate whatever the completion speed, `concurrency` then only caps the running tasks.
+  ///
+  /// Without a rate, a task is launched whenever one finishes.
+  pub rate: Option<Rate>,
+  pub arrivals: Arrivals,
+  /// What to do with launches arriving while `concurrency` tasks are running.
+  pub saturation: Saturation,
+  /// Delay between the initial launches that fill the pool up to `concurrency`.
+  pub launch_delay: Duration,
+  /// Time limit of each task.
+  pub timeout: Option<Duration>,
+  /// What a task must do to count as successful.
+  pub success: SuccessCriteria,
+  /// Time a timed out task gets to exit after SIGTERM, before its process group is killed.
+  pub kill_grace: Duration,
+  /// Number of times a failed task is retried before it counts as failed.
+  pub retries: usize,
+  /// Delay before the first retry, grown according to `backoff`.
+  pub retry_delay: Duration,
+  pub backoff: Backoff,
+  /// Send every output line as a [`crate::PoolEvent::TaskOutput`] as soon as it is printed.
+  pub stream_output: bool,
+  /// Stop spawning tasks after the first failure, same as a `max_failures` of 1.
+  pub stop_on_fail: bool,
+  /// Failure counts past which no new task is launched.
+  pub failure_thresholds: FailureThresholds,
+  /// Whether the running tasks are killed or left to finish when a failure threshold or `stop_on_fail` stops the run.
+  pub stop_mode: StopMode,
+  /// Environment of the tasks instead of the inherited one, the `COMMAND_POOL_*` variables being added to it.
+  pub env: Option<Vec<(String, String)>>,
+  /// Identifies this run, exposed to tasks as `COMMAND_POOL_RUN_ID`.
+  pub run_id: String,
+}
+
+impl PoolConfig {
+  /// Runs `command` `total_tasks` times, one task at a time.
+  pub fn new(command: Vec<String>, total_tasks: usize) -> Self {
+    PoolConfig {
+      command,
+      inputs: None,
+      total_tasks: Some(total_tasks),
+      skip_tasks: HashSet::new(),
+      duration: None,
+      concurrency: 1,
+      profile: None,
+      rate: None,
+      arrivals: Arrivals::default(),
+      saturation: Saturation::default(),
+      launch_delay: Duration::ZERO,
+      timeout: None,
+      success: SuccessCriteria::default(),
+      kill_grace: Duration::from_secs(5),
+      retries: 0,
+      retry_delay: Duration::from_secs(1),
+      backoff: Backoff::default(),
+      stream_output: false,
+      stop_on_fail: false,
+      failure_thresholds: FailureThresholds::default(),
+      stop_mode: StopMode::default(),
+      env: None,
+      run_id: generate_run_id(),
+    }
+  }
+
+  /// Runs `command` until the run is interrupted or `duration` is over.
+  pub fn unbounded(command: Vec<String>) -> Self {
+    let mut config = PoolConfig::new(command, 0);
+    config.total_tasks = None;
+    config
+  }
+
+  /// Runs `command` once per input line, see [`crate::template`] for how the input is passed.
+  pub fn with_inputs(command: Vec<String>, inputs: Vec<String>) -> Self {
+    let mut config = PoolConfig::new(command, inputs.len());
+    config.inputs = Some(inputs);
+    config
+  }
+
+  /// Number of tasks the run launches: `total_tasks` without the skipped ones, `None` for unbounded runs.
+  pub fn tasks_to_run(&self) -> Option<usize> {
+    self.total_tasks.map(|total_tasks| {
+      let skipped = self.skip_tasks.iter().filter(|&&id| (1..=total_tasks).contains(&id)).count();
+      total_tasks - skipped
+    })
+  }
+}
+
+fn generate_run_id() -> String {
+  let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+  format!("{millis:x}-{:x}", std::process::id())
+}
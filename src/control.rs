@@ -0,0 +1,92 @@
+//! Runtime control of a running pool through a Unix domain socket, for `--control-socket`.
+//!
+//! Clients send one command per line and get one JSON object per line back, e.g.
+//! `echo "concurrency 4" | socat - UNIX-CONNECT:pool.sock`.
+
+use command_pool::PoolHandle;
+use serde_json::{Value, json};
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::json;
+
+const HELP: &str = "commands: stats, concurrency [N], pause, resume, stop, cancel TASK_ID, help";
+
+/// Binds the socket at `path` and serves the commands sent to it for the rest of the process.
+///
+/// A socket left behind at `path` by an earlier run is replaced, any other file is an error.
+pub fn listen(path: &Path, handle: PoolHandle) -> std::io::Result<()> {
+  if let Ok(metadata) = std::fs::symlink_metadata(path)
+    && metadata.file_type().is_socket()
+  {
+    std::fs::remove_file(path)?;
+  }
+  let listener = UnixListener::bind(path)?;
+  tokio::spawn(async move {
+    while let Ok((stream, _)) = listener.accept().await {
+      tokio::spawn(serve(stream, handle.clone()));
+    }
+  });
+  Ok(())
+}
+
+async fn serve(stream: UnixStream, handle: PoolHandle) {
+  let (reader, mut writer) = stream.into_split();
+  let mut lines = BufReader::new(reader).lines();
+  while let Ok(Some(line)) = lines.next_line().await {
+    if line.trim().is_empty() {
+      continue;
+    }
+    let response = format!("{}\n", execute(&line, &handle));
+    if writer.write_all(response.as_bytes()).await.is_err() {
+      break;
+    }
+  }
+}
+
+fn execute(command: &str, handle: &PoolHandle) -> Value {
+  let words: Vec<&str> = command.split_whitespace().collect();
+  match words.as_slice() {
+    ["stats"] => {
+      let mut stats = json::stats(&handle.snapshot());
+      stats["running"] = handle.running().into();
+      stats["concurrency"] = handle.concurrency().into();
+      stats["paused"] = handle.is_paused().into();
+      json!({ "ok": true, "stats": stats })
+    }
+    ["concurrency"] => json!({ "ok": true, "concurrency": handle.concurrency() }),
+    ["concurrency", concurrency] => match concurrency.parse::<usize>() {
+      Ok(0) => error("concurrency must be positive".to_string()),
+      Ok(concurrency) => {
+        handle.set_concurrency(concurrency);
+        json!({ "ok": true, "concurrency": concurrency })
+      }
+      Err(_) => error(format!("invalid concurrency `{concurrency}`")),
+    },
+    ["pause"] => {
+      handle.pause();
+      json!({ "ok": true, "paused": true })
+    }
+    ["resume"] => {
+      handle.resume();
+      json!({ "ok": true, "paused": false })
+    }
+    ["stop"] => {
+      handle.stop();
+      json!({ "ok": true })
+    }
+    ["cancel", task_id] => match task_id.parse::<usize>() {
+      Ok(task_id) if handle.cancel(task_id) => json!({ "ok": true, "task_id": task_id }),
+      Ok(task_id) => error(format!("task {task_id} is not running")),
+      Err(_) => error(format!("invalid task id `{task_id}`")),
+    },
+    ["help"] => json!({ "ok": true, "help": HELP }),
+    _ => error(format!("unknown command `{}`, {HELP}", command.trim())),
+  }
+}
+
+fn error(message: String) -> Value {
+  json!({ "ok": false, "error": message })
+}
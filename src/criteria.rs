@@ -0,0 +1,140 @@
+use regex::Regex;
+use std::time::Duration;
+
+/// What a task must do to count as successful, beyond exiting.
+#[derive(Debug, Clone)]
+pub struct SuccessCriteria {
+  /// Exit codes that count as success, only zero by default.
+  pub exit_codes: Vec<i32>,
+  /// The stdout of the task must match.
+  pub expect_stdout: Option<Regex>,
+  /// The task fails if its stderr matches, whatever its exit code.
+  pub fail_on_stderr: Option<Regex>,
+  /// The task fails if it runs longer, even when it completes. Unlike the timeout, it is not stopped.
+  pub max_duration: Option<Duration>,
+}
+
+impl Default for SuccessCriteria {
+  fn default() -> Self {
+    SuccessCriteria {
+      exit_codes: vec![0],
+      expect_stdout: None,
+      fail_on_stderr: None,
+      max_duration: None,
+    }
+  }
+}
+
+impl SuccessCriteria {
+  pub fn accepts_exit_code(&self, exit_code: i32) -> bool {
+    self.exit_codes.contains(&exit_code)
+  }
+
+  /// Checks a task that exited with an accepted code, returns why it fails if it does.
+  pub fn check(&self, stdout: &str, stderr: &str, duration: Duration) -> Option<String> {
+    if let Some(max_duration) = self.max_duration
+      && duration > max_duration
+    {
+      return Some(format!("ran longer than {}", humantime::format_duration(max_duration)));
+    }
+    if let Some(expected) = &self.expect_stdout
+      && !expected.is_match(stdout)
+    {
+      return Some(format!("stdout does not match `{expected}`"));
+    }
+    if let Some(failure) = &self.fail_on_stderr
+      && failure.is_match(stderr)
+    {
+      return Some(format!("stderr matches `{failure}`"));
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SECOND: Duration = Duration::from_secs(1);
+
+  fn regex(pattern: &str) -> Option<Regex> {
+    Some(Regex::new(pattern).unwrap())
+  }
+
+  #[test]
+  fn only_zero_is_accepted_by_default() {
+    let criteria = SuccessCriteria::default();
+    assert!(criteria.accepts_exit_code(0));
+    assert!(!criteria.accepts_exit_code(1));
+    assert_eq!(criteria.check("", "", 3600 * SECOND), None);
+  }
+
+  #[test]
+  fn accepts_the_configured_exit_codes_only() {
+    let criteria = SuccessCriteria {
+      exit_codes: vec![0, 3],
+      ..SuccessCriteria::default()
+    };
+    assert!(criteria.accepts_exit_code(3));
+    assert!(!criteria.accepts_exit_code(1));
+    let criteria = SuccessCriteria {
+      exit_codes: vec![3],
+      ..SuccessCriteria::default()
+    };
+    assert!(!criteria.accepts_exit_code(0));
+  }
+
+  #[test]
+  fn max_duration_is_exclusive() {
+    let criteria = SuccessCriteria {
+      max_duration: Some(2 * SECOND),
+      ..SuccessCriteria::default()
+    };
+    assert_eq!(criteria.check("", "", 2 * SECOND), None);
+    assert_eq!(criteria.check("", "", 3 * SECOND), Some("ran longer than 2s".to_string()));
+  }
+
+  #[test]
+  fn stdout_must_match() {
+    let criteria = SuccessCriteria {
+      expect_stdout: regex("^ok$"),
+      ..SuccessCriteria::default()
+    };
+    assert_eq!(
+      criteria.check("checking\nok\n", "", SECOND),
+      Some("stdout does not match `^ok$`".to_string())
+    );
+    let criteria = SuccessCriteria {
+      expect_stdout: regex("(?m)^ok$"),
+      ..SuccessCriteria::default()
+    };
+    assert_eq!(criteria.check("checking\nok\n", "", SECOND), None);
+  }
+
+  #[test]
+  fn stderr_must_not_match() {
+    let criteria = SuccessCriteria {
+      fail_on_stderr: regex("(?i)error"),
+      ..SuccessCriteria::default()
+    };
+    assert_eq!(criteria.check("", "warning: slow\n", SECOND), None);
+    assert_eq!(
+      criteria.check("", "ERROR: disk full\n", SECOND),
+      Some("stderr matches `(?i)error`".to_string())
+    );
+  }
+
+  #[test]
+  fn criteria_are_checked_in_order() {
+    let criteria = SuccessCriteria {
+      expect_stdout: regex("ok"),
+      fail_on_stderr: regex("error"),
+      max_duration: Some(SECOND),
+      ..SuccessCriteria::default()
+    };
+    assert_eq!(criteria.check("", "error", 2 * SECOND), Some("ran longer than 1s".to_string()));
+    assert_eq!(criteria.check("", "error", SECOND), Some("stdout does not match `ok`".to_string()));
+    assert_eq!(criteria.check("ok", "error", SECOND), Some("stderr matches `error`".to_string()));
+    assert_eq!(criteria.check("ok", "", SECOND), None);
+  }
+}
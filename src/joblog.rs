@@ -0,0 +1,315 @@
+//! Append-only job log for `--joblog`, read back by `--resume` to skip the tasks that already succeeded
+//! and by `command-pool rerun --failed` to run the failed ones again.
+//!
+//! Every run starts with a `{"event": "run", ...}` record holding its arguments, working directory and environment,
+//! followed by one record per finished task.
+
+use command_pool::TaskResult;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use crate::json;
+
+/// Job log being written, one JSON object per finished task.
+pub struct JobLog {
+  file: File,
+  run_id: String,
+}
+
+impl JobLog {
+  /// Opens the log for appending and records the start of a run, `args` and `env` being what reproduces it
+  /// from the current directory.
+  pub async fn open(
+    path: &Path,
+    run_id: &str,
+    args: &[String],
+    env: &[(String, String)],
+    total_tasks: Option<usize>,
+  ) -> std::io::Result<Self> {
+    let cwd = std::env::current_dir()?;
+    // The run record holds the environment, which may contain credentials
+    let file = OpenOptions::new().create(true).append(true).mode(0o600).open(path).await?;
+    let mut joblog = JobLog {
+      file,
+      run_id: run_id.to_string(),
+    };
+    let run = json!({
+      "event": "run",
+      "run_id": run_id,
+      "args": args,
+      "cwd": cwd.to_string_lossy(),
+      "env": env.iter().map(|(key, value)| (key.clone(), Value::from(value.as_str()))).collect::<serde_json::Map<_, _>>(),
+      "total_tasks": total_tasks,
+      "started_at": humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+    });
+    joblog.write_line(&run).await?;
+    Ok(joblog)
+  }
+
+  /// Appends the final result of a task, in a single write so that a crash cannot interleave lines.
+  pub async fn append(&mut self, result: &TaskResult) -> std::io::Result<()> {
+    let mut value = json::task_result(result);
+    value["run_id"] = self.run_id.as_str().into();
+    self.write_line(&value).await
+  }
+
+  async fn write_line(&mut self, value: &Value) -> std::io::Result<()> {
+    self.file.write_all(format!("{value}\n").as_bytes()).await?;
+    self.file.flush().await
+  }
+}
+
+/// A task recorded in a job log.
+pub struct Entry {
+  pub task_id: usize,
+  pub input: Option<String>,
+  pub success: bool,
+}
+
+/// Reads the last record of every task in a job log, by task id, a missing log has none.
+///
+/// Lines that cannot be parsed, like one cut short by a crash, are skipped.
+pub fn read(path: &Path) -> std::io::Result<Vec<Entry>> {
+  let content = match std::fs::read_to_string(path) {
+    Ok(content) => content,
+    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+    Err(e) => return Err(e),
+  };
+  Ok(last_entries(content.lines().filter_map(parse_entry)).into_values().collect())
+}
+
+/// Keeps the last record of every task, a task resumed or rerun later superseding its earlier records.
+fn last_entries(entries: impl Iterator<Item = Entry>) -> BTreeMap<usize, Entry> {
+  entries.map(|entry| (entry.task_id, entry)).collect()
+}
+
+/// What `command-pool rerun --failed` needs from a job log.
+pub struct Rerun {
+  /// Arguments of the last run recorded in the log.
+  pub args: Vec<String>,
+  /// Working directory of that run, `None` for logs written before it was recorded.
+  pub cwd: Option<PathBuf>,
+  /// Environment of that run, `None` for logs written before it was recorded.
+  pub env: Option<Vec<(String, String)>>,
+  /// Tasks of the original run, the highest task id for duration based runs.
+  pub total_tasks: usize,
+  /// Tasks whose last record failed, by task id.
+  pub failed: BTreeMap<usize, Entry>,
+  /// Number of tasks whose last record succeeded.
+  pub succeeded: usize,
+  /// Input of every task id, empty for the tasks that never ran.
+  pub inputs: Vec<String>,
+}
+
+impl Rerun {
+  /// Every task id but the failed ones.
+  pub fn skip_tasks(&self) -> HashSet<usize> {
+    (1..=self.total_tasks).filter(|id| !self.failed.contains_key(id)).collect()
+  }
+}
+
+pub fn read_rerun(path: &Path) -> Result<Rerun, String> {
+  let content = std::fs::read_to_string(path).map_err(|e| format!("could not read the job log {}: {e}", path.display()))?;
+  let mut run = None;
+  let mut entries = Vec::new();
+  for line in content.lines() {
+    if let Some(entry) = parse_entry(line) {
+      entries.push(entry);
+    } else if let Ok(value) = serde_json::from_str::<Value>(line)
+      && value["event"] == "run"
+    {
+      run = Some(value);
+    }
+  }
+  let mut last_entries = last_entries(entries.into_iter());
+  let run = run.ok_or_else(|| format!("{} has no run record, was it written by --joblog?", path.display()))?;
+  let args = run["args"]
+    .as_array()
+    .and_then(|args| args.iter().map(|arg| arg.as_str().map(str::to_string)).collect::<Option<Vec<_>>>())
+    .ok_or_else(|| format!("the run record of {} has no arguments", path.display()))?;
+  let cwd = run["cwd"].as_str().map(PathBuf::from);
+  let env = run["env"].as_object().map(|env| {
+    env
+      .iter()
+      .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+      .collect()
+  });
+  let highest_id = last_entries.keys().next_back().copied().unwrap_or_default();
+  let total_tasks = run["total_tasks"].as_u64().map_or(highest_id, |total_tasks| total_tasks as usize);
+  // Records of an earlier, larger run sharing the log cannot be rerun with the arguments of the last one
+  last_entries.retain(|task_id, _| (1..=total_tasks).contains(task_id));
+
+  let mut inputs = vec![String::new(); total_tasks];
+  for entry in last_entries.values() {
+    if let Some(input) = &entry.input {
+      inputs[entry.task_id - 1].clone_from(input);
+    }
+  }
+  let succeeded = last_entries.values().filter(|entry| entry.success).count();
+  let failed = last_entries.into_iter().filter(|(_, entry)| !entry.success).collect();
+  Ok(Rerun {
+    args,
+    cwd,
+    env,
+    total_tasks,
+    failed,
+    succeeded,
+    inputs,
+  })
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+  let value: Value = serde_json::from_str(line).ok()?;
+  Some(Entry {
+    task_id: value["task_id"].as_u64()? as usize,
+    input: value["input"].as_str().map(str::to_string),
+    success: value["success"].as_bool()?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Writes `log` to a temporary file and reads it back for a rerun.
+  fn read_log(name: &str, log: &str) -> Result<Rerun, String> {
+    let path = std::env::temp_dir().join(format!("command-pool-{}-{name}.jsonl", std::process::id()));
+    std::fs::write(&path, log).unwrap();
+    let rerun = read_rerun(&path);
+    std::fs::remove_file(&path).unwrap();
+    rerun
+  }
+
+  #[test]
+  fn resume_reads_the_last_record_of_every_task() {
+    let path = std::env::temp_dir().join(format!("command-pool-{}-resume.jsonl", std::process::id()));
+    let log = r#"{"event":"run","run_id":"a","args":["-n","3","--","true"],"total_tasks":3}
+{"task_id":1,"input":null,"success":true}
+{"task_id":2,"input":null,"success":true}
+{"task_id":3,"input":null,"success":false}
+{"event":"run","run_id":"b","args":["-n","3","--","true"],"total_tasks":3}
+{"task_id":2,"input":null,"success":false}
+{"task_id":3,"input":null,"success":true}
+"#;
+    std::fs::write(&path, log).unwrap();
+    let entries = read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let succeeded: Vec<usize> = entries.iter().filter(|entry| entry.success).map(|entry| entry.task_id).collect();
+    assert_eq!(succeeded, [1, 3]);
+  }
+
+  #[test]
+  fn resume_without_a_log_skips_nothing() {
+    let path = std::env::temp_dir().join(format!("command-pool-{}-missing.jsonl", std::process::id()));
+    assert!(read(&path).unwrap().is_empty());
+  }
+
+  fn failed_ids(rerun: &Rerun) -> Vec<usize> {
+    rerun.failed.keys().copied().collect()
+  }
+
+  #[test]
+  fn reruns_the_failed_tasks_of_the_last_run() {
+    let log = r#"{"event":"run","run_id":"a","args":["-n","4","--","false"],"total_tasks":4}
+{"task_id":1,"input":null,"success":true}
+{"task_id":2,"input":null,"success":false}
+{"task_id":3,"input":null,"success":false}
+{"event":"run","run_id":"b","args":["-n","3","--","true"],"total_tasks":3}
+{"task_id":1,"input":null,"success":false}
+"#;
+    let rerun = read_log("last-run", log).unwrap();
+    assert_eq!(rerun.args, ["-n", "3", "--", "true"]);
+    assert_eq!(rerun.total_tasks, 3);
+    assert_eq!(failed_ids(&rerun), [1, 2, 3]);
+    assert_eq!(rerun.succeeded, 0);
+    assert_eq!(rerun.skip_tasks(), HashSet::new());
+  }
+
+  #[test]
+  fn the_last_record_of_a_task_wins() {
+    let log = r#"{"event":"run","run_id":"a","args":["-a","in.txt","--","cat"],"total_tasks":3}
+{"task_id":1,"input":"x","success":false}
+{"task_id":2,"input":"y","success":true}
+{"task_id":3,"input":"z","success":false}
+{"event":"run","run_id":"b","args":["-a","in.txt","--","cat"],"total_tasks":3}
+{"task_id":1,"input":"x","success":true}
+{"task_id":2,"input":"y","success":false}
+"#;
+    let rerun = read_log("last-record", log).unwrap();
+    assert_eq!(failed_ids(&rerun), [2, 3]);
+    assert_eq!(rerun.succeeded, 1);
+    assert_eq!(rerun.inputs, ["x", "y", "z"]);
+    assert_eq!(rerun.skip_tasks(), HashSet::from([1]));
+  }
+
+  #[test]
+  fn ignores_tasks_beyond_the_last_run() {
+    let log = r#"{"event":"run","run_id":"a","args":["-n","10","--","false"],"total_tasks":10}
+{"task_id":9,"input":null,"success":false}
+{"task_id":10,"input":null,"success":true}
+{"event":"run","run_id":"b","args":["-n","2","--","false"],"total_tasks":2}
+{"task_id":2,"input":null,"success":false}
+"#;
+    let rerun = read_log("beyond", log).unwrap();
+    assert_eq!(rerun.total_tasks, 2);
+    assert_eq!(failed_ids(&rerun), [2]);
+    assert_eq!(rerun.succeeded, 0);
+    assert_eq!(rerun.inputs.len(), 2);
+  }
+
+  #[test]
+  fn unbounded_runs_go_up_to_the_highest_task_id() {
+    let log = r#"{"event":"run","run_id":"a","args":["--duration","1m","--","false"],"total_tasks":null}
+{"task_id":1,"input":null,"success":true}
+{"task_id":5,"input":null,"success":false}
+"#;
+    let rerun = read_log("unbounded", log).unwrap();
+    assert_eq!(rerun.total_tasks, 5);
+    assert_eq!(failed_ids(&rerun), [5]);
+    assert_eq!(rerun.skip_tasks(), HashSet::from([1, 2, 3, 4]));
+  }
+
+  #[test]
+  fn skips_truncated_lines() {
+    let log = r#"{"event":"run","run_id":"a","args":["-n","3","--","false"],"total_tasks":3}
+{"task_id":1,"input":null,"success":false}
+{"task_id":2,"input":null,"succ
+{"task_id":3,"input":null,"success":false}
+{"task_id":2,"#;
+    let rerun = read_log("truncated", log).unwrap();
+    assert_eq!(failed_ids(&rerun), [1, 3]);
+  }
+
+  #[test]
+  fn restores_the_directory_and_environment() {
+    let log = r#"{"event":"run","run_id":"a","args":["-n","1","--","env"],"cwd":"/srv/batch","env":{"HOME":"/root","LANG":"C"},"total_tasks":1}
+{"task_id":1,"input":null,"success":false}
+"#;
+    let rerun = read_log("env", log).unwrap();
+    assert_eq!(rerun.cwd, Some(PathBuf::from("/srv/batch")));
+    let env = [("HOME", "/root"), ("LANG", "C")].map(|(key, value)| (key.to_string(), value.to_string()));
+    assert_eq!(rerun.env, Some(env.to_vec()));
+
+    // Logs written before the directory and environment were recorded
+    let log = r#"{"event":"run","run_id":"a","args":["-n","1","--","env"],"total_tasks":1}
+{"task_id":1,"input":null,"success":false}
+"#;
+    let rerun = read_log("no-env", log).unwrap();
+    assert_eq!((rerun.cwd, rerun.env), (None, None));
+  }
+
+  #[test]
+  fn needs_a_run_record() {
+    let log = r#"{"task_id":1,"input":null,"success":false}
+"#;
+    let Err(e) = read_log("no-run", log) else {
+      panic!("a log without run record should not be rerun");
+    };
+    assert!(e.contains("has no run record"), "{e}");
+  }
+}
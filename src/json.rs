@@ -0,0 +1,207 @@
+//! JSON renderings of the pool events and of the summary, for `--output-format json|jsonl`.
+
+use command_pool::{
+  DurationStats, OutputStream, PhaseSummary, PoolEvent, PoolSummary, ResourceUsage, TaskResult, TaskSpec, UsageStats, ValueStats,
+};
+use serde_json::{Value, json};
+use std::time::SystemTime;
+
+use crate::joblog::Rerun;
+use crate::{HISTOGRAM_BUCKETS, PERCENTILES};
+
+pub fn event(event: &PoolEvent) -> Value {
+  match event {
+    PoolEvent::TaskStarted { task, running } => {
+      let mut value = task_fields(task);
+      value["event"] = "task_started".into();
+      value["running"] = (*running).into();
+      value
+    }
+    PoolEvent::TaskOutput { task_id, stream, line } => json!({
+      "event": "task_output",
+      "task_id": task_id,
+      "stream": match stream {
+        OutputStream::Stdout => "stdout",
+        OutputStream::Stderr => "stderr",
+      },
+      "line": line,
+    }),
+    PoolEvent::TaskRetrying { result, delay } => {
+      let mut value = task_result(result);
+      value["event"] = "task_retrying".into();
+      value["retry_delay_secs"] = delay.as_secs_f64().into();
+      value
+    }
+    PoolEvent::TaskFinished { result, running } => {
+      let mut value = task_result(result);
+      value["event"] = "task_finished".into();
+      value["running"] = (*running).into();
+      value
+    }
+  }
+}
+
+fn task_fields(task: &TaskSpec) -> Value {
+  json!({
+    "task_id": task.id,
+    "slot": task.slot,
+    "input": task.input,
+    "command": task.command_line(),
+  })
+}
+
+/// Everything known about a finished task but its output.
+pub fn task_result(result: &TaskResult) -> Value {
+  let mut value = task_fields(&result.task);
+  value["attempt"] = result.attempt.into();
+  value["success"] = result.outcome.is_success().into();
+  value["outcome"] = result.outcome.to_string().into();
+  value["outcome_kind"] = result.outcome.kind().into();
+  value["exit_code"] = result.exit_code().into();
+  value["signal"] = result.signal().into();
+  value["started_at"] = timestamp(result.started_at).into();
+  value["finished_at"] = timestamp(result.finished_at()).into();
+  value["duration_secs"] = result.duration.as_secs_f64().into();
+  value["usage"] = result.usage.as_ref().map(usage).into();
+  value["stdout_bytes"] = result.stdout.len().into();
+  value["stderr_bytes"] = result.stderr.len().into();
+  value
+}
+
+pub fn summary(summary: &PoolSummary, run_id: &str, exit_code: u8) -> Value {
+  let mut value = stats(summary);
+  value["run_id"] = run_id.into();
+  value["exit_code"] = exit_code.into();
+  value
+}
+
+/// Statistics of a run, finished or not.
+pub fn stats(summary: &PoolSummary) -> Value {
+  json!({
+    "total_tasks": summary.total_tasks,
+    "completed": summary.completed,
+    "successful": summary.successful,
+    "failed": summary.failed,
+    "timed_out": summary.timed_out,
+    "succeeded_after_retry": summary.succeeded_after_retry,
+    "retries": summary.retries,
+    "delayed_launches": summary.delayed_launches,
+    "skipped_launches": summary.skipped_launches,
+    "success_rate": summary.success_rate(),
+    "stopped_early": summary.stopped_early,
+    "stop_reason": summary.stop_reason,
+    "interrupted": summary.interrupted,
+    "total_duration_secs": summary.total_duration.as_secs_f64(),
+    "successful_durations": duration_stats(&summary.successful_durations),
+    "failed_durations": duration_stats(&summary.failed_durations),
+    "outcomes": summary
+      .outcomes
+      .iter()
+      .map(|(kind, durations)| (kind.to_string(), duration_stats(durations)))
+      .collect::<serde_json::Map<_, _>>(),
+    "exit_codes": exit_codes(summary),
+    "usage": usage_stats(&summary.usage),
+    "phases": summary.phases.iter().map(phase).collect::<Vec<_>>(),
+  })
+}
+
+/// Results of a rerun added to the tasks that succeeded before it.
+pub fn combined(rerun: &Rerun, summary: &PoolSummary) -> Value {
+  let succeeded = rerun.succeeded + summary.successful;
+  json!({
+    "total_tasks": rerun.total_tasks,
+    "succeeded_before": rerun.succeeded,
+    "rerun_tasks": rerun.failed.len(),
+    "rerun_succeeded": summary.successful,
+    "still_failing": rerun.failed.len() - summary.successful,
+    "succeeded": succeeded,
+    "success_rate": succeeded as f64 / rerun.total_tasks.max(1) as f64 * 100.0,
+  })
+}
+
+fn phase(phase: &PhaseSummary) -> Value {
+  json!({
+    "phase": phase.phase.to_string(),
+    "duration_secs": phase.phase.duration().as_secs_f64(),
+    "completed": phase.completed,
+    "successful": phase.successful,
+    "failed": phase.failed,
+    "failure_rate": phase.failure_rate(),
+    "durations": duration_stats(&phase.durations),
+  })
+}
+
+/// Number of tasks by exit code, nested under their outcome kind: `{"unaccepted_exit": {"1": 3}}`.
+fn exit_codes(summary: &PoolSummary) -> Value {
+  let mut kinds = serde_json::Map::new();
+  for ((kind, exit_code), tasks) in &summary.exit_codes {
+    let codes = kinds.entry(kind.to_string()).or_insert_with(|| json!({}));
+    codes[exit_code.to_string()] = (*tasks).into();
+  }
+  kinds.into()
+}
+
+fn usage(usage: &ResourceUsage) -> Value {
+  json!({
+    "max_rss_bytes": usage.max_rss,
+    "user_time_secs": usage.user_time.as_secs_f64(),
+    "system_time_secs": usage.system_time.as_secs_f64(),
+    "voluntary_context_switches": usage.voluntary_context_switches,
+    "involuntary_context_switches": usage.involuntary_context_switches,
+  })
+}
+
+fn usage_stats(usage: &UsageStats) -> Value {
+  json!({
+    "max_rss_bytes": value_stats(&usage.max_rss),
+    "user_time": duration_stats(&usage.user_time),
+    "system_time": duration_stats(&usage.system_time),
+    "voluntary_context_switches": value_stats(&usage.voluntary_context_switches),
+    "involuntary_context_switches": value_stats(&usage.involuntary_context_switches),
+  })
+}
+
+fn value_stats(values: &ValueStats) -> Value {
+  if values.is_empty() {
+    return json!({ "count": 0 });
+  }
+  let mut percentiles = serde_json::Map::new();
+  for percentile in PERCENTILES {
+    percentiles.insert(format!("p{percentile}"), values.percentile(percentile).into());
+  }
+  json!({
+    "count": values.count(),
+    "mean": values.mean(),
+    "min": values.min(),
+    "max": values.max(),
+    "percentiles": percentiles,
+  })
+}
+
+fn duration_stats(durations: &DurationStats) -> Value {
+  if durations.is_empty() {
+    return json!({ "count": 0 });
+  }
+  let mut percentiles = serde_json::Map::new();
+  for percentile in PERCENTILES {
+    percentiles.insert(format!("p{percentile}"), durations.percentile(percentile).as_secs_f64().into());
+  }
+  let histogram: Vec<Value> = durations
+    .buckets(HISTOGRAM_BUCKETS)
+    .into_iter()
+    .map(|(upper, tasks)| json!({ "le_secs": upper.as_secs_f64(), "count": tasks }))
+    .collect();
+  json!({
+    "count": durations.count(),
+    "mean_secs": durations.mean().as_secs_f64(),
+    "min_secs": durations.min().as_secs_f64(),
+    "max_secs": durations.max().as_secs_f64(),
+    "stdev_secs": durations.stdev().as_secs_f64(),
+    "percentiles_secs": percentiles,
+    "histogram": histogram,
+  })
+}
+
+fn timestamp(time: SystemTime) -> String {
+  humantime::format_rfc3339_millis(time).to_string()
+}
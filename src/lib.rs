@@ -0,0 +1,47 @@
+//! The engine behind the `command-pool` binary: run a command template many times with bounded concurrency.
+//!
+//! ```no_run
+//! use command_pool::{Pool, PoolConfig, PoolEvent};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut config = PoolConfig::new(vec!["echo".to_string(), "task {#}".to_string()], 10);
+//! config.concurrency = 4;
+//!
+//! let mut pool = Pool::new(config);
+//! let mut events = pool.events();
+//! let runner = tokio::spawn(pool.run());
+//! while let Some(event) = events.recv().await {
+//!   if let PoolEvent::TaskFinished { result, .. } = event {
+//!     println!("task {}: {}", result.task.id, result.outcome);
+//!   }
+//! }
+//! let summary = runner.await??;
+//! println!("{} of {} tasks succeeded", summary.successful, summary.completed);
+//! # Ok(())
+//! # }
+//! ```
+
+mod backoff;
+mod config;
+mod criteria;
+mod pool;
+mod process;
+mod profile;
+mod rate;
+mod stats;
+mod summary;
+mod task;
+pub mod template;
+mod threshold;
+
+pub use backoff::Backoff;
+pub use config::PoolConfig;
+pub use criteria::SuccessCriteria;
+pub use pool::{Pool, PoolEvent, PoolHandle};
+pub use process::ResourceUsage;
+pub use profile::{LoadProfile, Phase};
+pub use rate::{Arrivals, Rate, Saturation};
+pub use stats::{DurationStats, UsageStats, ValueStats};
+pub use summary::{PhaseSummary, PoolSummary};
+pub use task::{OutputStream, TaskOutcome, TaskResult, TaskSpec, Termination};
+pub use threshold::{FailureThresholds, StopMode};
@@ -1,26 +1,114 @@
+mod control;
+mod joblog;
+mod json;
+mod progress;
+mod task_logs;
+#[cfg(feature = "tui")]
+mod tui;
+
 use argh::FromArgs;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
-use std::sync::Mutex;
-use tokio::process::Command;
-use tokio::task::JoinSet;
-use tokio::time::{self, Duration, Instant};
+use command_pool::{
+  Arrivals, Backoff, DurationStats, LoadProfile, OutputStream, Pool, PoolConfig, PoolEvent, PoolHandle, PoolSummary, Rate, Saturation,
+  StopMode, TaskResult, ValueStats,
+};
+use regex::Regex;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::mpsc;
+use tokio::time;
 
 #[derive(FromArgs, Debug)]
 /// a command-pool to run multiple commands in parallel.
+#[argh(note = "Use `command-pool rerun --failed JOBLOG` to rerun the failed tasks of a run logged with --joblog.")]
 struct Args {
   /// number of concurrent tasks
   #[argh(option, short = 'c', default = "1")]
   concurrency: usize,
 
-  /// total number of tasks to execute
+  /// read the concurrency from this file instead of -c, and read it again on SIGHUP
+  #[argh(option)]
+  concurrency_file: Option<PathBuf>,
+
+  /// vary the concurrency over time instead of -c, e.g. "ramp 0->32 over 2m, hold 10m, ramp 32->0 over 1m", the run ends with the profile
+  #[argh(option)]
+  profile: Option<LoadProfile>,
+
+  /// launch tasks at this rate whatever the completion speed, e.g. "50/s", "300/m" or "10/h", concurrency then only caps the running tasks
+  #[argh(option)]
+  rate: Option<Rate>,
+
+  /// distribution of the launches with --rate: uniform or poisson
+  #[argh(option, default = "Arrivals::Uniform")]
+  arrivals: Arrivals,
+
+  /// what happens to a --rate launch while concurrency tasks are running: delay it until a task finishes, or skip it
+  #[argh(option, default = "Saturation::Delay")]
+  on_saturation: Saturation,
+
+  /// total number of tasks to execute (derived from the input lines when --arg-file is given)
   #[argh(option, short = 'n')]
-  total_tasks: usize,
+  total_tasks: Option<usize>,
+
+  /// keep the pool saturated for this long, e.g. "10m", then let the running tasks finish
+  #[argh(option, from_str_fn(parse_duration))]
+  duration: Option<Duration>,
+
+  /// keep launching tasks until interrupted
+  #[argh(switch)]
+  forever: bool,
+
+  /// read per-task arguments line by line from a file ("-" for stdin), each line is appended to the command unless it uses a {} placeholder
+  #[argh(option, short = 'a')]
+  arg_file: Option<String>,
 
   /// hide some-command specific stdout output, only show task start/end info
   #[argh(switch, short = 'q')]
   quiet: bool,
 
+  /// buffer the output of each task and print it when the task finishes, instead of streaming it line by line
+  #[argh(switch, short = 'g')]
+  group: bool,
+
+  /// write the stdout, stderr and metadata of each task to DIR/<task id>/ instead of printing its output
+  #[argh(option)]
+  output_dir: Option<PathBuf>,
+
+  /// append the result of every task to this file, one JSON object per line
+  #[argh(option)]
+  joblog: Option<PathBuf>,
+
+  /// skip the tasks that --joblog records as successful, to continue an interrupted run
+  #[argh(switch)]
+  resume: bool,
+
+  /// with --output-dir, also print the task output
+  #[argh(switch)]
+  tee: bool,
+
+  /// show a full-screen dashboard of the running tasks and recent failures, with keys to pause, change the concurrency, kill or dump a task (needs the tui feature)
+  #[argh(switch)]
+  tui: bool,
+
+  /// listen on this Unix socket for commands adjusting the run: stats, concurrency [N], pause, resume, stop, cancel TASK_ID
+  #[argh(option)]
+  control_socket: Option<PathBuf>,
+
+  /// do not show the progress line, which is otherwise redrawn at the bottom of the terminal when stdout is a TTY
+  #[argh(switch)]
+  no_progress: bool,
+
+  /// prefix streamed output lines with a timestamp
+  #[argh(switch)]
+  timestamps: bool,
+
+  /// colour the task prefix of the output lines, one colour per task
+  #[argh(switch)]
+  color: bool,
+
   /// delay between initial task launches in milliseconds
   #[argh(option, short = 'd', default = "100")]
   delay: u64,
@@ -29,15 +117,139 @@ struct Args {
   #[argh(option)]
   timeout: Option<u64>,
 
+  /// seconds a timed out task gets to exit after SIGTERM before its process group is killed
+  #[argh(option, default = "5")]
+  kill_grace: u64,
+
+  /// number of times a failed task is retried before it counts as failed
+  #[argh(option, default = "0")]
+  retries: usize,
+
+  /// delay before the first retry in milliseconds
+  #[argh(option, default = "1000")]
+  retry_delay: u64,
+
+  /// how the retry delay grows: fixed, exponential or jitter
+  #[argh(option, default = "Backoff::Exponential")]
+  retry_backoff: Backoff,
+
+  /// comma separated exit codes that count as success, e.g. "0,3" (default: 0)
+  #[argh(option, from_str_fn(parse_exit_codes))]
+  success_exit_codes: Option<Vec<i32>>,
+
+  /// fail the tasks whose stdout does not match this regex
+  #[argh(option)]
+  expect_stdout: Option<Regex>,
+
+  /// fail the tasks whose stderr matches this regex, whatever their exit code
+  #[argh(option)]
+  fail_on_stderr: Option<Regex>,
+
+  /// fail the tasks that run longer than this, e.g. "2s", without stopping them like --timeout does
+  #[argh(option, from_str_fn(parse_duration))]
+  max_duration: Option<Duration>,
+
   /// stop on first failure
   #[argh(switch)]
   stop_on_fail: bool,
 
-  /// the command and its arguments to execute
+  /// stop launching tasks once this many failed
+  #[argh(option)]
+  max_failures: Option<usize>,
+
+  /// stop launching tasks when more than this share of the last --failure-window tasks failed, e.g. "5%"
+  #[argh(option, from_str_fn(parse_percent))]
+  max_failure_rate: Option<f64>,
+
+  /// number of completed tasks --max-failure-rate is evaluated over, it is only checked once that many completed
+  #[argh(option, default = "100")]
+  failure_window: usize,
+
+  /// stop launching tasks once this many failed in a row
+  #[argh(option)]
+  fail_fast_after_consecutive: Option<usize>,
+
+  /// what happens to the running tasks when a failure threshold or --stop-on-fail stops the run: abort (kill them) or drain (let them finish)
+  #[argh(option, default = "StopMode::Abort")]
+  stop_mode: StopMode,
+
+  /// only fail the run when the success rate in percent is below this threshold, by default any failure fails the run
+  #[argh(option)]
+  min_success_rate: Option<f64>,
+
+  /// output format: text, json (a summary document) or jsonl (one event per line, then the summary)
+  #[argh(option, default = "OutputFormat::Text")]
+  output_format: OutputFormat,
+
+  /// the command and its arguments to execute, may contain {}, {.}, {/}, {//}, {/.}, {#} and {%} placeholders
   #[argh(positional, greedy)]
   command: Vec<String>,
 }
 
+#[derive(FromArgs, Debug)]
+/// rerun the failed tasks of a previous run with its command, options, directory and environment: command-pool rerun --failed JOBLOG
+struct RerunArgs {
+  /// job log of the previous run, written with --joblog
+  #[argh(option)]
+  failed: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+  Text,
+  Json,
+  Jsonl,
+}
+
+impl FromStr for OutputFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(OutputFormat::Text),
+      "json" => Ok(OutputFormat::Json),
+      "jsonl" => Ok(OutputFormat::Jsonl),
+      other => Err(format!("unknown output format `{other}`, expected text, json or jsonl")),
+    }
+  }
+}
+
+// Exit codes of command-pool.
+/// Every task succeeded, or the success rate met `--min-success-rate`.
+const EXIT_SUCCESS: u8 = 0;
+/// Some tasks failed.
+const EXIT_TASKS_FAILED: u8 = 1;
+/// `--stop-on-fail` or a failure threshold stopped the run early.
+const EXIT_STOPPED_EARLY: u8 = 2;
+/// Some tasks failed and at least one of them timed out.
+const EXIT_TIMED_OUT: u8 = 3;
+/// command-pool itself failed, e.g. the argument file could not be read.
+const EXIT_INTERNAL_ERROR: u8 = 4;
+/// The arguments or the configuration are invalid, following the `EX_USAGE` convention of sysexits.h.
+const EXIT_USAGE: u8 = 64;
+/// The run was interrupted by SIGINT or SIGTERM, following the 128 + SIGINT shell convention.
+const EXIT_INTERRUPTED: u8 = 130;
+
+fn exit_code(summary: &PoolSummary, min_success_rate: Option<f64>) -> u8 {
+  if summary.interrupted {
+    return EXIT_INTERRUPTED;
+  }
+  if summary.stopped_early {
+    return EXIT_STOPPED_EARLY;
+  }
+  let run_failed = match min_success_rate {
+    Some(threshold) => summary.success_rate() < threshold,
+    None => summary.failed > 0,
+  };
+  if !run_failed {
+    EXIT_SUCCESS
+  } else if summary.timed_out > 0 {
+    EXIT_TIMED_OUT
+  } else {
+    EXIT_TASKS_FAILED
+  }
+}
+
 fn format_duration_custom(duration: Duration) -> String {
   let secs = duration.as_secs();
   if secs >= 60 {
@@ -47,298 +259,833 @@ fn format_duration_custom(duration: Duration) -> String {
   }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-  let args: Args = argh::from_env();
+fn parse_duration(value: &str) -> Result<Duration, String> {
+  humantime::parse_duration(value).map_err(|e| format!("invalid duration `{value}`: {e}"))
+}
 
-  if args.command.is_empty() {
-    eprintln!("Error: No command provided to execute.");
-    std::process::exit(1);
+fn parse_percent(value: &str) -> Result<f64, String> {
+  let percent: f64 = value
+    .trim()
+    .trim_end_matches('%')
+    .parse()
+    .map_err(|_| format!("invalid percentage `{value}`"))?;
+  if !(0.0..=100.0).contains(&percent) {
+    return Err(format!("percentage `{value}` is not between 0 and 100"));
   }
+  Ok(percent)
+}
 
-  let command_str = args.command[0].clone();
-  let command_args = args.command[1..].to_vec();
+fn parse_exit_codes(value: &str) -> Result<Vec<i32>, String> {
+  value
+    .split(',')
+    .map(|code| code.trim().parse().map_err(|_| format!("invalid exit code `{code}`")))
+    .collect()
+}
 
-  println!("Starting command-pool with:");
-  println!("  Concurrency: {}", args.concurrency);
-  println!("  Total tasks: {}", args.total_tasks);
-  println!("  Command: {} {}", command_str, command_args.join(" "));
-  println!("  Quiet mode: {}", args.quiet);
-  println!("  Initial launch delay: {}ms", args.delay);
-  println!("----------------------------------------");
+/// Reads one task input per non-empty line, from stdin when `path` is "-".
+fn read_task_inputs(path: &str) -> std::io::Result<Vec<String>> {
+  let content = if path == "-" {
+    let mut buf = String::new();
+    std::io::stdin().read_to_string(&mut buf)?;
+    buf
+  } else {
+    std::fs::read_to_string(path)?
+  };
+  Ok(content.lines().filter(|line| !line.trim().is_empty()).map(str::to_string).collect())
+}
+
+const PERCENTILES: [f64; 5] = [50.0, 90.0, 95.0, 99.0, 99.9];
+const HISTOGRAM_BUCKETS: usize = 10;
+const HISTOGRAM_WIDTH: u64 = 40;
+/// How often the progress line is redrawn when no event comes in.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+fn print_duration_stats(title: &str, durations: &DurationStats) {
+  if durations.is_empty() {
+    return;
+  }
+  println!("\n{title}:");
+  println!("  Average Duration: {}", format_duration_custom(durations.mean()));
+  println!("  Min Duration: {}", format_duration_custom(durations.min()));
+  println!("  Max Duration: {}", format_duration_custom(durations.max()));
+  println!("  Std Deviation: {}", format_duration_custom(durations.stdev()));
+  for percentile in PERCENTILES {
+    println!("  p{percentile}: {}", format_duration_custom(durations.percentile(percentile)));
+  }
 
-  let start_time = Instant::now(); // Overall start time
-
-  let mut join_set = JoinSet::new();
-  let completed_tasks = Arc::new(AtomicUsize::new(0));
-  let successful_tasks = Arc::new(AtomicUsize::new(0));
-  let failed_tasks = Arc::new(AtomicUsize::new(0));
-  let running_tasks = Arc::new(AtomicUsize::new(0));
-  let successful_durations = Arc::new(Mutex::new(Vec::<Duration>::new())); // New: Store successful task durations
-  let failed_durations = Arc::new(Mutex::new(Vec::<Duration>::new())); // New: Store failed task durations
-  let stop_spawning = Arc::new(AtomicBool::new(false));
-
-  let mut task_id_counter = 0;
-
-  // Spawn initial tasks up to concurrency limit
-  for i in 0..args.concurrency.min(args.total_tasks) {
-    task_id_counter += 1;
-    let task_id = task_id_counter;
-    let cmd_str_clone = command_str.clone();
-    let cmd_args_clone = command_args.clone();
-    let quiet_clone = args.quiet;
-    let completed_tasks_clone = Arc::clone(&completed_tasks);
-    let successful_tasks_clone = Arc::clone(&successful_tasks);
-    let failed_tasks_clone = Arc::clone(&failed_tasks);
-    let running_tasks_clone = Arc::clone(&running_tasks);
-    let successful_durations_clone = Arc::clone(&successful_durations);
-    let failed_durations_clone = Arc::clone(&failed_durations);
-    let timeout_clone = args.timeout;
-    let stop_on_fail_clone = args.stop_on_fail;
-    let stop_spawning_clone = Arc::clone(&stop_spawning);
-
-    join_set.spawn(async move {
-      running_tasks_clone.fetch_add(1, Ordering::SeqCst);
+  let buckets = durations.buckets(HISTOGRAM_BUCKETS);
+  let largest = buckets.iter().map(|&(_, tasks)| tasks).max().unwrap_or_default().max(1);
+  println!("  Histogram:");
+  for (upper, tasks) in buckets {
+    let bar = "#".repeat((tasks * HISTOGRAM_WIDTH).div_ceil(largest) as usize);
+    println!("    <= {:>8} | {bar} {tasks}", format_duration_custom(upper));
+  }
+}
+
+/// How task events are printed in text mode.
+struct TextOptions {
+  quiet: bool,
+  /// Print the task output, which is off when it only goes to `--output-dir`.
+  show_output: bool,
+  group: bool,
+  timestamps: bool,
+  color: bool,
+}
+
+impl TextOptions {
+  /// The `[Task N]` prefix of every task line, coloured by task id with `--color`.
+  fn prefix(&self, task_id: usize) -> String {
+    if self.color {
+      // Cycle through red, green, yellow, blue, magenta and cyan
+      format!("\x1b[3{}m[Task {task_id}]\x1b[0m", 1 + (task_id - 1) % 6)
+    } else {
+      format!("[Task {task_id}]")
+    }
+  }
+
+  fn print_line(&self, task_id: usize, stream: OutputStream, line: &str) {
+    let timestamp = if self.timestamps {
+      format!("{} ", humantime::format_rfc3339_millis(SystemTime::now()))
+    } else {
+      String::new()
+    };
+    match stream {
+      OutputStream::Stdout if !self.quiet => println!("{timestamp}{} {line}", self.prefix(task_id)),
+      OutputStream::Stdout => {}
+      OutputStream::Stderr => eprintln!("{timestamp}{} {line}", self.prefix(task_id)),
+    }
+  }
+
+  /// Prints the buffered output of a task, only in `--group` mode as it was streamed otherwise.
+  fn print_output(&self, result: &TaskResult) {
+    if !self.group || !self.show_output {
+      return;
+    }
+    let prefix = self.prefix(result.task.id);
+    if !self.quiet && !result.stdout.is_empty() {
       println!(
-        "[Task {}] Starting... (Running: {})",
-        task_id,
-        running_tasks_clone.load(Ordering::SeqCst)
+        "{prefix} Stdout:
+{}",
+        result.stdout
       );
-      let mut cmd = Command::new(&cmd_str_clone);
-      cmd.args(&cmd_args_clone);
-
-      let task_start_time = Instant::now(); // Task start time
-      let output_result = if let Some(timeout_secs) = timeout_clone {
-        match tokio::time::timeout(Duration::from_secs(timeout_secs), cmd.output()).await {
-          Ok(res) => res,
-          Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Task timed out")),
+    }
+    if !result.stderr.is_empty() {
+      eprintln!(
+        "{prefix} Stderr:
+{}",
+        result.stderr
+      );
+    }
+  }
+
+  fn print_event(&self, event: PoolEvent) {
+    match event {
+      PoolEvent::TaskStarted { task, running } => {
+        println!("{} Starting... (Running: {})", self.prefix(task.id), running);
+      }
+      PoolEvent::TaskOutput { task_id, stream, line } => self.print_line(task_id, stream, &line),
+      PoolEvent::TaskRetrying { result, delay } => {
+        println!(
+          "{} Attempt {} failed: {}, retrying in {}",
+          self.prefix(result.task.id),
+          result.attempt,
+          result.outcome,
+          format_duration_custom(delay)
+        );
+        self.print_output(&result);
+      }
+      PoolEvent::TaskFinished { result, running } => {
+        let prefix = self.prefix(result.task.id);
+        if result.attempt > 1 {
+          println!(
+            "{prefix} Finished: {} after {} attempts (Running: {})",
+            result.outcome, result.attempt, running
+          );
+        } else {
+          println!("{prefix} Finished: {} (Running: {})", result.outcome, running);
         }
-      } else {
-        cmd.output().await
+        self.print_output(&result);
+      }
+    }
+  }
+}
+
+/// The first SIGINT or SIGTERM stops spawning and forwards the signal to the running tasks, the next one kills them.
+fn handle_interrupts(handle: PoolHandle) -> std::io::Result<()> {
+  let mut sigint = signal(SignalKind::interrupt())?;
+  let mut sigterm = signal(SignalKind::terminate())?;
+  tokio::spawn(async move {
+    let mut interrupted = false;
+    loop {
+      let received = tokio::select! {
+        Some(()) = sigint.recv() => libc::SIGINT,
+        Some(()) = sigterm.recv() => libc::SIGTERM,
+        else => break,
       };
-      let task_duration = task_start_time.elapsed(); // Task duration
-
-      let (result_msg, stdout_output, stderr_output) = match output_result {
-        Ok(output) => {
-          let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-          let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-          if output.status.success() {
-            successful_tasks_clone.fetch_add(1, Ordering::SeqCst);
-            successful_durations_clone.lock().unwrap().push(task_duration); // Store duration
-            (
-              format!("Success (Exit Code: {})", output.status.code().unwrap_or_default()),
-              stdout,
-              stderr,
-            )
-          } else {
-            failed_tasks_clone.fetch_add(1, Ordering::SeqCst);
-            if stop_on_fail_clone {
-              stop_spawning_clone.store(true, Ordering::SeqCst);
-            }
-            failed_durations_clone.lock().unwrap().push(task_duration); // Store duration
-            (
-              format!("Failed (Exit Code: {})", output.status.code().unwrap_or_default()),
-              stdout,
-              stderr,
-            )
+      if interrupted {
+        eprintln!("Killing running tasks...");
+        handle.kill();
+      } else {
+        interrupted = true;
+        eprintln!("Interrupted, waiting for running tasks to finish (interrupt again to kill them)...");
+        handle.interrupt(received);
+      }
+    }
+  });
+  Ok(())
+}
+
+/// SIGUSR1 pauses or resumes launching, SIGUSR2 prints the statistics so far to stderr, SIGHUP reads the `--concurrency-file` again.
+///
+/// Without a concurrency file SIGHUP is left alone, so that a terminal hangup still ends command-pool.
+fn handle_control_signals(handle: PoolHandle, concurrency_file: Option<PathBuf>) -> std::io::Result<()> {
+  if let Some(path) = concurrency_file {
+    let mut sighup = signal(SignalKind::hangup())?;
+    let handle = handle.clone();
+    tokio::spawn(async move {
+      while sighup.recv().await.is_some() {
+        match read_concurrency(&path) {
+          Ok(concurrency) => {
+            handle.set_concurrency(concurrency);
+            eprintln!("Concurrency set to {concurrency} from {}.", path.display());
           }
+          Err(e) => eprintln!("Warning: keeping the concurrency at {}: {e}", handle.concurrency()),
         }
-        Err(e) => {
-          failed_tasks_clone.fetch_add(1, Ordering::SeqCst);
-          if stop_on_fail_clone {
-            stop_spawning_clone.store(true, Ordering::SeqCst);
+      }
+    });
+  }
+  let mut sigusr1 = signal(SignalKind::user_defined1())?;
+  let mut sigusr2 = signal(SignalKind::user_defined2())?;
+  tokio::spawn(async move {
+    loop {
+      tokio::select! {
+        Some(()) = sigusr1.recv() => {
+          if handle.is_paused() {
+            handle.resume();
+            eprintln!("Resumed launching tasks.");
+          } else {
+            handle.pause();
+            eprintln!("Paused launching tasks, the running ones keep going (SIGUSR1 again to resume).");
           }
-          failed_durations_clone.lock().unwrap().push(task_duration); // Store duration
-          (format!("Error: {e}"), String::new(), String::new())
         }
-      };
+        Some(()) = sigusr2.recv() => eprint_snapshot(&handle),
+        else => break,
+      }
+    }
+  });
+  Ok(())
+}
 
-      completed_tasks_clone.fetch_add(1, Ordering::SeqCst);
-      running_tasks_clone.fetch_sub(1, Ordering::SeqCst);
-      println!(
-        "[Task {}] Finished: {} (Running: {})",
-        task_id,
-        result_msg,
-        running_tasks_clone.load(Ordering::SeqCst)
+/// Reads a positive concurrency from the first line of a file.
+fn read_concurrency(path: &Path) -> Result<usize, String> {
+  let content = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {e}", path.display()))?;
+  match content.trim().parse() {
+    Ok(concurrency) if concurrency > 0 => Ok(concurrency),
+    _ => Err(format!("{} does not contain a positive concurrency", path.display())),
+  }
+}
+
+/// Prints the statistics of the run so far on a few lines of stderr.
+fn eprint_snapshot(handle: &PoolHandle) {
+  let summary = handle.snapshot();
+  eprintln!(
+    "Stats after {}: {} completed, {} running, {} successful, {} failed, {:.2}% success rate, concurrency {}{}",
+    format_duration_custom(summary.total_duration),
+    summary.completed,
+    handle.running(),
+    summary.successful,
+    summary.failed,
+    summary.success_rate(),
+    handle.concurrency(),
+    if handle.is_paused() { " (paused)" } else { "" }
+  );
+  for (title, durations) in [("successful", &summary.successful_durations), ("failed", &summary.failed_durations)] {
+    if !durations.is_empty() {
+      eprintln!(
+        "  {title} durations: mean {}, p50 {}, p95 {}, p99 {}",
+        format_duration_custom(durations.mean()),
+        format_duration_custom(durations.percentile(50.0)),
+        format_duration_custom(durations.percentile(95.0)),
+        format_duration_custom(durations.percentile(99.0))
       );
-      if !quiet_clone && !stdout_output.is_empty() {
-        println!(
-          "[Task {task_id}] Stdout:
-{stdout_output}"
-        );
+    }
+  }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+  let argv: Vec<String> = std::env::args().collect();
+  let (args, rerun) = if argv.get(1).is_some_and(|arg| arg == "rerun") {
+    let rerun_args: RerunArgs = parse_args("command-pool rerun", &argv[2..]);
+    let rerun = match joblog::read_rerun(&rerun_args.failed) {
+      Ok(rerun) => rerun,
+      Err(e) => {
+        eprintln!("Error: {e}");
+        std::process::exit(EXIT_USAGE.into());
       }
-      if !stderr_output.is_empty() {
+    };
+    if rerun.failed.is_empty() {
+      println!("No failed task in {}, nothing to rerun.", rerun_args.failed.display());
+      return ExitCode::from(EXIT_SUCCESS);
+    }
+    // Relative paths in the arguments, like --arg-file or --joblog, are relative to the original directory
+    if let Some(cwd) = &rerun.cwd
+      && let Err(e) = std::env::set_current_dir(cwd)
+    {
+      eprintln!("Error: could not enter the directory {} of the original run: {e}", cwd.display());
+      std::process::exit(EXIT_USAGE.into());
+    }
+    let mut args: Args = parse_args("command-pool", &rerun.args);
+    // Run exactly the failed task ids, whatever bounded the original run
+    if args.arg_file.is_none() {
+      args.total_tasks = Some(rerun.total_tasks);
+    }
+    args.duration = None;
+    args.forever = false;
+    args.profile = None;
+    args.resume = false;
+    (args, Some(rerun))
+  } else {
+    (parse_args("command-pool", &argv[1..]), None)
+  };
+
+  if args.command.is_empty() {
+    eprintln!("Error: No command provided to execute.");
+    std::process::exit(EXIT_USAGE.into());
+  }
+  if let Some(rate) = args.min_success_rate
+    && !(0.0..=100.0).contains(&rate)
+  {
+    eprintln!("Error: --min-success-rate must be between 0 and 100.");
+    std::process::exit(EXIT_USAGE.into());
+  }
+  if args.concurrency == 0 {
+    eprintln!("Error: --concurrency must be at least 1.");
+    std::process::exit(EXIT_USAGE.into());
+  }
+  if args.max_failure_rate.is_some() && args.failure_window == 0 {
+    eprintln!("Error: --failure-window must be at least 1 with --max-failure-rate.");
+    std::process::exit(EXIT_USAGE.into());
+  }
+
+  if args.tui && !cfg!(feature = "tui") {
+    eprintln!("Error: --tui needs command-pool to be built with the tui feature.");
+    std::process::exit(EXIT_USAGE.into());
+  }
+  if args.tui && args.output_format != OutputFormat::Text {
+    eprintln!("Error: --tui cannot be combined with --output-format json or jsonl.");
+    std::process::exit(EXIT_USAGE.into());
+  }
+
+  match run(args, rerun).await {
+    Ok(code) => ExitCode::from(code),
+    Err(e) => {
+      eprintln!("Error: {e}");
+      ExitCode::from(EXIT_INTERNAL_ERROR)
+    }
+  }
+}
+
+/// Parses `args` with argh, printing the help or the error and exiting on failure like `argh::from_env`.
+fn parse_args<T: FromArgs>(command: &str, args: &[String]) -> T {
+  let args: Vec<&str> = args.iter().map(String::as_str).collect();
+  T::from_args(&[command], &args).unwrap_or_else(|early_exit| match early_exit.status {
+    Ok(()) => {
+      println!("{}", early_exit.output);
+      std::process::exit(0);
+    }
+    Err(()) => {
+      eprintln!("{}\nRun {command} --help for more information.", early_exit.output);
+      std::process::exit(EXIT_USAGE.into());
+    }
+  })
+}
+
+async fn run(args: Args, rerun: Option<joblog::Rerun>) -> Result<u8, Box<dyn std::error::Error>> {
+  let task_inputs = match (&args.arg_file, &rerun) {
+    // The inputs may have come from stdin, rerun the ones recorded in the job log
+    (Some(_), Some(rerun)) => Some(rerun.inputs.clone()),
+    (Some(path), None) => Some(read_task_inputs(path)?),
+    (None, _) => None,
+  };
+  let mut config = match (task_inputs, args.total_tasks) {
+    (Some(_), Some(_)) => {
+      eprintln!("Error: --total-tasks cannot be combined with --arg-file.");
+      std::process::exit(EXIT_USAGE.into());
+    }
+    (Some(inputs), None) => PoolConfig::with_inputs(args.command.clone(), inputs),
+    (None, Some(n)) => PoolConfig::new(args.command.clone(), n),
+    (None, None) if args.duration.is_some() || args.forever || args.profile.is_some() => PoolConfig::unbounded(args.command.clone()),
+    (None, None) => {
+      eprintln!("Error: one of --total-tasks, --arg-file, --duration, --profile or --forever is required.");
+      std::process::exit(EXIT_USAGE.into());
+    }
+  };
+  if args.forever && (config.total_tasks.is_some() || args.duration.is_some() || args.profile.is_some()) {
+    eprintln!("Error: --forever cannot be combined with --total-tasks, --arg-file, --duration or --profile.");
+    std::process::exit(EXIT_USAGE.into());
+  }
+  if args.profile.as_ref().is_some_and(|profile| profile.peak() == 0) {
+    eprintln!("Error: --profile never goes above a concurrency of 0.");
+    std::process::exit(EXIT_USAGE.into());
+  }
+  config.duration = args.duration;
+  config.concurrency = match &args.concurrency_file {
+    Some(path) => read_concurrency(path).unwrap_or_else(|e| {
+      eprintln!("Error: {e}");
+      std::process::exit(EXIT_USAGE.into());
+    }),
+    None => args.concurrency,
+  };
+  config.profile = args.profile.clone();
+  config.rate = args.rate;
+  config.arrivals = args.arrivals;
+  config.saturation = args.on_saturation;
+  config.launch_delay = Duration::from_millis(args.delay);
+  config.timeout = args.timeout.map(Duration::from_secs);
+  config.kill_grace = Duration::from_secs(args.kill_grace);
+  config.retries = args.retries;
+  if let Some(exit_codes) = &args.success_exit_codes {
+    config.success.exit_codes = exit_codes.clone();
+  }
+  config.success.expect_stdout = args.expect_stdout.clone();
+  config.success.fail_on_stderr = args.fail_on_stderr.clone();
+  config.success.max_duration = args.max_duration;
+  config.retry_delay = Duration::from_millis(args.retry_delay);
+  config.backoff = args.retry_backoff;
+  let show_output = args.output_dir.is_none() || args.tee;
+  // The dashboard keeps the output of the running tasks to dump them
+  config.stream_output = args.tui || (args.output_format == OutputFormat::Text && !args.group && show_output);
+  config.stop_on_fail = args.stop_on_fail;
+  config.failure_thresholds.max_failures = args.max_failures;
+  config.failure_thresholds.max_failure_rate = args.max_failure_rate;
+  config.failure_thresholds.window = args.failure_window;
+  config.failure_thresholds.max_consecutive = args.fail_fast_after_consecutive;
+  config.stop_mode = args.stop_mode;
+  if args.resume {
+    let Some(path) = &args.joblog else {
+      eprintln!("Error: --resume needs --joblog.");
+      std::process::exit(EXIT_USAGE.into());
+    };
+    if config.total_tasks.is_none() {
+      eprintln!("Error: --resume needs --total-tasks or --arg-file.");
+      std::process::exit(EXIT_USAGE.into());
+    }
+    for entry in joblog::read(path)?.into_iter().filter(|entry| entry.success) {
+      if let Some(inputs) = &config.inputs
+        && entry.task_id.checked_sub(1).and_then(|idx| inputs.get(idx)) != entry.input.as_ref()
+      {
         eprintln!(
-          "[Task {task_id}] Stderr:
-{stderr_output}"
+          "Error: task {} of the job log {} ran with another input, it does not match --arg-file.",
+          entry.task_id,
+          path.display()
         );
+        std::process::exit(EXIT_USAGE.into());
       }
-      task_id
+      config.skip_tasks.insert(entry.task_id);
+    }
+  }
+  if let Some(rerun) = &rerun {
+    config.skip_tasks.extend(rerun.skip_tasks());
+    config.env.clone_from(&rerun.env);
+  }
+  let run_id = config.run_id.clone();
+  let tasks_to_run = config.tasks_to_run();
+
+  if args.output_format == OutputFormat::Text {
+    print_header(&args, &config, rerun.as_ref());
+  }
+
+  if let Some(dir) = &args.output_dir {
+    std::fs::create_dir_all(dir)?;
+  }
+
+  let text = TextOptions {
+    quiet: args.quiet,
+    show_output,
+    group: args.group,
+    timestamps: args.timestamps,
+    color: args.color,
+  };
+  let progress =
+    (args.output_format == OutputFormat::Text && !args.tui && !args.no_progress && std::io::stdout().is_terminal()).then(|| {
+      let profile_length = config.profile.as_ref().map(|profile| profile.duration());
+      progress::Progress::new(tasks_to_run, config.duration.into_iter().chain(profile_length).min())
     });
+  // Reruns log the arguments and environment of the original run, so that they can be rerun again
+  let logged_args = match &rerun {
+    Some(rerun) => rerun.args.clone(),
+    None => std::env::args().skip(1).collect(),
+  };
+  let logged_env = match &config.env {
+    Some(env) => env.clone(),
+    // Variables that are not valid UTF-8 cannot be logged as JSON strings
+    None => std::env::vars_os()
+      .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+      .collect(),
+  };
+  let mut recorder = TaskRecorder {
+    output_dir: args.output_dir.clone(),
+    joblog: match &args.joblog {
+      Some(path) => Some(
+        joblog::JobLog::open(path, &run_id, &logged_args, &logged_env, config.total_tasks)
+          .await
+          .map_err(|e| format!("could not open the job log {}: {e}", path.display()))?,
+      ),
+      None => None,
+    },
+  };
+  let mut pool = Pool::new(config);
+  let events = pool.events();
+  handle_interrupts(pool.handle())?;
+  handle_control_signals(pool.handle(), args.concurrency_file.clone())?;
+  if let Some(path) = &args.control_socket {
+    control::listen(path, pool.handle()).map_err(|e| format!("could not listen on the control socket {}: {e}", path.display()))?;
+  }
+  #[cfg(feature = "tui")]
+  let handle = pool.handle();
+  let runner = tokio::spawn(pool.run());
+  if args.tui {
+    #[cfg(feature = "tui")]
+    tui::run(handle, events, tasks_to_run, &mut recorder).await?;
+  } else {
+    print_events(&args, &text, events, progress, &mut recorder).await;
+  }
+  let summary = runner.await??;
+  if let Some(path) = &args.control_socket {
+    let _ = std::fs::remove_file(path);
+  }
 
-    // Apply delay only for initial launches, and not after the last initial task
-    if args.delay > 0 && i < args.concurrency.min(args.total_tasks) - 1 {
-      time::sleep(Duration::from_millis(args.delay)).await;
+  let code = exit_code(&summary, args.min_success_rate);
+  match args.output_format {
+    OutputFormat::Text => {
+      print_summary(&summary);
+      if let Some(rerun) = &rerun {
+        print_combined_report(rerun, &summary);
+      }
+    }
+    OutputFormat::Json | OutputFormat::Jsonl => {
+      let mut value = json::summary(&summary, &run_id, code);
+      if let Some(rerun) = &rerun {
+        value["combined"] = json::combined(rerun, &summary);
+      }
+      if args.output_format == OutputFormat::Jsonl {
+        value["event"] = "summary".into();
+        println!("{value}");
+      } else {
+        println!("{}", serde_json::to_string_pretty(&value)?);
+      }
     }
   }
+  Ok(code)
+}
 
-  // Continuously spawn new tasks as old ones complete, until total_tasks is reached
-  while let Some(res) = join_set.join_next().await {
-    let _finished_task_id = res?; // Handle potential panics in spawned tasks
+/// Adds the tasks that succeeded before the rerun to its results.
+fn print_combined_report(rerun: &joblog::Rerun, summary: &PoolSummary) {
+  let succeeded = rerun.succeeded + summary.successful;
+  println!("\nCombined Report:");
+  println!("  Succeeded before the rerun: {}", rerun.succeeded);
+  println!("  Rerun: {} of {} failed tasks succeeded", summary.successful, rerun.failed.len());
+  println!("  Still failing: {}", rerun.failed.len() - summary.successful);
+  println!(
+    "  Overall: {succeeded} of {} tasks succeeded ({:.2}%)",
+    rerun.total_tasks,
+    succeeded as f64 / rerun.total_tasks.max(1) as f64 * 100.0
+  );
+}
 
-    if stop_spawning.load(Ordering::SeqCst) {
-      break;
-    }
+/// Where finished tasks are recorded besides the printed events.
+struct TaskRecorder {
+  output_dir: Option<PathBuf>,
+  joblog: Option<joblog::JobLog>,
+}
 
-    if task_id_counter < args.total_tasks {
-      task_id_counter += 1;
-      let task_id = task_id_counter;
-      let cmd_str_clone = command_str.clone();
-      let cmd_args_clone = command_args.clone();
-      let quiet_clone = args.quiet;
-      let completed_tasks_clone = Arc::clone(&completed_tasks);
-      let successful_tasks_clone = Arc::clone(&successful_tasks);
-      let failed_tasks_clone = Arc::clone(&failed_tasks);
-      let running_tasks_clone = Arc::clone(&running_tasks);
-      let successful_durations_clone = Arc::clone(&successful_durations);
-      let failed_durations_clone = Arc::clone(&failed_durations);
-      let timeout_clone = args.timeout;
-      let stop_on_fail_clone = args.stop_on_fail;
-      let stop_spawning_clone = Arc::clone(&stop_spawning);
-
-      join_set.spawn(async move {
-        running_tasks_clone.fetch_add(1, Ordering::SeqCst);
-        println!(
-          "[Task {}] Starting... (Running: {})",
-          task_id,
-          running_tasks_clone.load(Ordering::SeqCst)
-        );
-        let mut cmd = Command::new(&cmd_str_clone);
-        cmd.args(&cmd_args_clone);
-
-        let task_start_time = Instant::now(); // Task start time
-        let output_result = if let Some(timeout_secs) = timeout_clone {
-          match tokio::time::timeout(Duration::from_secs(timeout_secs), cmd.output()).await {
-            Ok(res) => res,
-            Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Task timed out")),
-          }
-        } else {
-          cmd.output().await
-        };
-        let task_duration = task_start_time.elapsed(); // Task duration
-
-        let (result_msg, stdout_output, stderr_output) = match output_result {
-          Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            if output.status.success() {
-              successful_tasks_clone.fetch_add(1, Ordering::SeqCst);
-              successful_durations_clone.lock().unwrap().push(task_duration); // Store duration
-              (
-                format!("Success (Exit Code: {})", output.status.code().unwrap_or_default()),
-                stdout,
-                stderr,
-              )
-            } else {
-              failed_tasks_clone.fetch_add(1, Ordering::SeqCst);
-              if stop_on_fail_clone {
-                stop_spawning_clone.store(true, Ordering::SeqCst);
-              }
-              failed_durations_clone.lock().unwrap().push(task_duration); // Store duration
-              (
-                format!("Failed (Exit Code: {})", output.status.code().unwrap_or_default()),
-                stdout,
-                stderr,
-              )
-            }
-          }
-          Err(e) => {
-            failed_tasks_clone.fetch_add(1, Ordering::SeqCst);
-            if stop_on_fail_clone {
-              stop_spawning_clone.store(true, Ordering::SeqCst);
-            }
-            failed_durations_clone.lock().unwrap().push(task_duration); // Store duration
-            (format!("Error: {e}"), String::new(), String::new())
-          }
-        };
+impl TaskRecorder {
+  /// Writes the task logs and the job log entry of a finished task, returns what could not be written.
+  async fn record(&mut self, result: &TaskResult) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if let Some(dir) = &self.output_dir
+      && let Err(e) = task_logs::write(dir, result).await
+    {
+      warnings.push(format!(
+        "could not write the logs of task {} to {}: {e}",
+        result.task.id,
+        dir.display()
+      ));
+    }
+    if let Some(joblog) = &mut self.joblog
+      && let Err(e) = joblog.append(result).await
+    {
+      warnings.push(format!("could not append task {} to the job log: {e}", result.task.id));
+    }
+    warnings
+  }
+}
 
-        completed_tasks_clone.fetch_add(1, Ordering::SeqCst);
-        running_tasks_clone.fetch_sub(1, Ordering::SeqCst);
-        println!(
-          "[Task {}] Finished: {} (Running: {})",
-          task_id,
-          result_msg,
-          running_tasks_clone.load(Ordering::SeqCst)
-        );
-        if !quiet_clone && !stdout_output.is_empty() {
-          println!(
-            "[Task {task_id}] Stdout:
-{stdout_output}"
-          );
+/// Prints the events of the run until it is over, in the text or jsonl format, and writes the task logs.
+async fn print_events(
+  args: &Args,
+  text: &TextOptions,
+  mut events: mpsc::UnboundedReceiver<PoolEvent>,
+  mut progress: Option<progress::Progress>,
+  recorder: &mut TaskRecorder,
+) {
+  let mut redraw = time::interval(PROGRESS_INTERVAL);
+  loop {
+    let event = tokio::select! {
+      event = events.recv() => match event {
+        Some(event) => event,
+        None => break,
+      },
+      _ = redraw.tick(), if progress.is_some() => {
+        if let Some(progress) = &progress {
+          progress.draw();
         }
-        if !stderr_output.is_empty() {
-          eprintln!(
-            "[Task {task_id}] Stderr:
-{stderr_output}"
-          );
+        continue;
+      }
+    };
+    if let PoolEvent::TaskFinished { result, .. } = &event {
+      for warning in recorder.record(result).await {
+        eprintln!("Warning: {warning}");
+      }
+    }
+    match args.output_format {
+      OutputFormat::Text => match &mut progress {
+        Some(progress) => {
+          progress.record(&event);
+          progress.clear();
+          text.print_event(event);
+          progress.draw();
         }
-        task_id
-      });
+        None => text.print_event(event),
+      },
+      OutputFormat::Json => {}
+      OutputFormat::Jsonl => println!("{}", json::event(&event)),
+    }
+  }
+  if let Some(progress) = &progress {
+    progress.clear();
+  }
+}
+
+fn print_header(args: &Args, config: &PoolConfig, rerun: Option<&joblog::Rerun>) {
+  println!("Starting command-pool with:");
+  println!("  Run ID: {}", config.run_id);
+  match &args.profile {
+    Some(profile) => println!("  Load profile: {profile}"),
+    None => println!("  Concurrency: {}", config.concurrency),
+  }
+  if let Some(rate) = args.rate {
+    println!("  Rate: {rate} ({} arrivals, {} on saturation)", args.arrivals, args.on_saturation);
+  }
+  match config.total_tasks {
+    Some(total_tasks) => println!("  Total tasks: {total_tasks}"),
+    None => println!("  Total tasks: unbounded"),
+  }
+  if let Some(duration) = config.duration {
+    println!("  Duration: {}", humantime::format_duration(duration));
+  }
+  if let Some(path) = &args.arg_file {
+    println!("  Argument input: {path}");
+  }
+  println!("  Command: {}", args.command.join(" "));
+  println!("  Quiet mode: {}", args.quiet);
+  println!("  Output: {}", if args.group { "grouped" } else { "streamed" });
+  if let Some(dir) = &args.output_dir {
+    println!("  Output directory: {}", dir.display());
+  }
+  if let Some(path) = &args.control_socket {
+    println!("  Control socket: {}", path.display());
+  }
+  if let Some(path) = &args.joblog {
+    println!("  Job log: {}", path.display());
+  }
+  if let Some(rerun) = rerun {
+    println!("  Rerunning {} failed tasks of {}", rerun.failed.len(), rerun.total_tasks);
+  } else if !config.skip_tasks.is_empty() {
+    println!("  Resumed: skipping {} tasks that already succeeded", config.skip_tasks.len());
+  }
+  println!("  Initial launch delay: {}ms", args.delay);
+  if let Some(timeout) = args.timeout {
+    println!("  Timeout: {timeout}s (kill grace: {}s)", args.kill_grace);
+  }
+  if let Some(exit_codes) = &args.success_exit_codes {
+    let exit_codes: Vec<String> = exit_codes.iter().map(i32::to_string).collect();
+    println!("  Success exit codes: {}", exit_codes.join(","));
+  }
+  if let Some(regex) = &args.expect_stdout {
+    println!("  Expected stdout: {regex}");
+  }
+  if let Some(regex) = &args.fail_on_stderr {
+    println!("  Failing stderr: {regex}");
+  }
+  if let Some(max_duration) = args.max_duration {
+    println!("  Max duration: {}", humantime::format_duration(max_duration));
+  }
+  let thresholds = &config.failure_thresholds;
+  let mut stop_after = Vec::new();
+  if let Some(max_failures) = thresholds.max_failures {
+    stop_after.push(format!("{max_failures} failures"));
+  }
+  if let Some(max_failure_rate) = thresholds.max_failure_rate {
+    stop_after.push(format!("{max_failure_rate}% failures over {} tasks", thresholds.window));
+  }
+  if let Some(max_consecutive) = thresholds.max_consecutive {
+    stop_after.push(format!("{max_consecutive} failures in a row"));
+  }
+  if !stop_after.is_empty() {
+    println!("  Stop after: {} ({})", stop_after.join(", "), config.stop_mode);
+  }
+  if args.retries > 0 {
+    println!(
+      "  Retries: {} ({} backoff from {}ms)",
+      args.retries, args.retry_backoff, args.retry_delay
+    );
+  }
+  println!("----------------------------------------");
+}
+
+/// Breaks the tasks down by outcome and by exit code, when some failed.
+fn print_outcomes(summary: &PoolSummary) {
+  if summary.failed == 0 {
+    return;
+  }
+  println!("\nOutcomes:");
+  for (kind, durations) in &summary.outcomes {
+    println!(
+      "  {kind}: {} tasks, p50 {}, p95 {}, max {}",
+      durations.count(),
+      format_duration_custom(durations.percentile(50.0)),
+      format_duration_custom(durations.percentile(95.0)),
+      format_duration_custom(durations.max())
+    );
+  }
+  if !summary.exit_codes.is_empty() {
+    println!("\nExit Codes:");
+    for ((kind, exit_code), tasks) in &summary.exit_codes {
+      println!("  {kind} {exit_code}: {tasks} tasks");
     }
+  }
+}
 
-    if completed_tasks.load(Ordering::SeqCst) == args.total_tasks {
-      break;
+fn print_usage(summary: &PoolSummary) {
+  let usage = &summary.usage;
+  if usage.is_empty() {
+    return;
+  }
+  let durations = |durations: &DurationStats| {
+    format!(
+      "mean {}, p50 {}, p95 {}, max {}",
+      format_duration_custom(durations.mean()),
+      format_duration_custom(durations.percentile(50.0)),
+      format_duration_custom(durations.percentile(95.0)),
+      format_duration_custom(durations.max())
+    )
+  };
+  let values = |values: &ValueStats, show: fn(u64) -> String| {
+    format!(
+      "mean {}, p50 {}, p95 {}, max {}",
+      show(values.mean() as u64),
+      show(values.percentile(50.0)),
+      show(values.percentile(95.0)),
+      show(values.max())
+    )
+  };
+  println!("\nResource Usage:");
+  println!("  Max RSS: {}", values(&usage.max_rss, format_bytes));
+  println!("  User CPU: {}", durations(&usage.user_time));
+  println!("  System CPU: {}", durations(&usage.system_time));
+  println!(
+    "  Voluntary Context Switches: {}",
+    values(&usage.voluntary_context_switches, |count| count.to_string())
+  );
+  println!(
+    "  Involuntary Context Switches: {}",
+    values(&usage.involuntary_context_switches, |count| count.to_string())
+  );
+}
+
+fn format_bytes(bytes: u64) -> String {
+  const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+  let mut value = bytes as f64;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < UNITS.len() - 1 {
+    value /= 1024.0;
+    unit += 1;
+  }
+  if unit == 0 {
+    format!("{bytes} B")
+  } else {
+    format!("{value:.1} {}", UNITS[unit])
+  }
+}
+
+fn print_phases(summary: &PoolSummary) {
+  if summary.phases.is_empty() {
+    return;
+  }
+  println!("\nLoad Profile Phases:");
+  for (idx, phase) in summary.phases.iter().enumerate() {
+    print!(
+      "  {}. {}: {} tasks, {:.2}% failed",
+      idx + 1,
+      phase.phase,
+      phase.completed,
+      phase.failure_rate()
+    );
+    if !phase.durations.is_empty() {
+      print!(
+        ", p50 {}, p95 {}, p99 {}",
+        format_duration_custom(phase.durations.percentile(50.0)),
+        format_duration_custom(phase.durations.percentile(95.0)),
+        format_duration_custom(phase.durations.percentile(99.0))
+      );
     }
+    println!();
   }
+}
 
-  if stop_spawning.load(Ordering::SeqCst) {
+fn print_summary(summary: &PoolSummary) {
+  if summary.stopped_early {
     println!("----------------------------------------");
-    println!("Execution stopped due to a task failure.");
-    join_set.abort_all();
+    match &summary.stop_reason {
+      Some(reason) => println!("Execution stopped early: {reason}."),
+      None => println!("Execution stopped due to a task failure."),
+    }
   }
 
-  let total_duration = start_time.elapsed(); // Overall end time
-
   println!("----------------------------------------");
-  println!("All tasks completed.");
-  println!("Total: {}", completed_tasks.load(Ordering::SeqCst));
-  println!("Successful: {}", successful_tasks.load(Ordering::SeqCst));
-  println!("Failed: {}", failed_tasks.load(Ordering::SeqCst));
-
-  let success_rate = if args.total_tasks > 0 {
-    (successful_tasks.load(Ordering::SeqCst) as f64 / args.total_tasks as f64) * 100.0
+  if summary.interrupted {
+    println!("Execution interrupted, the statistics cover the completed tasks only.");
   } else {
-    0.0
-  };
-  println!("Success Rate: {success_rate:.2}%");
-
-  // Report for successful tasks
-  let successful_durations_locked = successful_durations.lock().unwrap();
-  if !successful_durations_locked.is_empty() {
-    let sum_duration: Duration = successful_durations_locked.iter().sum();
-    let avg_duration = sum_duration / successful_durations_locked.len() as u32;
-    let min_duration = successful_durations_locked.iter().min().unwrap();
-    let max_duration = successful_durations_locked.iter().max().unwrap();
-    println!("\nSuccessful Tasks Statistics:");
-    println!("  Average Duration: {}", format_duration_custom(avg_duration));
-    println!("  Min Duration: {}", format_duration_custom(*min_duration));
-    println!("  Max Duration: {}", format_duration_custom(*max_duration));
-  }
-
-  // Report for failed tasks
-  let failed_durations_locked = failed_durations.lock().unwrap();
-  if !failed_durations_locked.is_empty() {
-    let sum_duration: Duration = failed_durations_locked.iter().sum();
-    let avg_duration = sum_duration / failed_durations_locked.len() as u32;
-    let min_duration = failed_durations_locked.iter().min().unwrap();
-    let max_duration = failed_durations_locked.iter().max().unwrap();
-    println!("\nFailed Tasks Statistics:");
-    println!("  Average Duration: {}", format_duration_custom(avg_duration));
-    println!("  Min Duration: {}", format_duration_custom(*min_duration));
-    println!("  Max Duration: {}", format_duration_custom(*max_duration));
-  }
-
-  println!("\nTotal command-pool execution time: {}", format_duration_custom(total_duration));
+    println!("All tasks completed.");
+  }
+  println!("Total: {}", summary.completed);
+  println!("Successful: {}", summary.successful);
+  println!("Failed: {}", summary.failed);
+  if summary.timed_out > 0 {
+    println!("Timed Out: {}", summary.timed_out);
+  }
+  println!("Success Rate: {:.2}%", summary.success_rate());
+  if summary.retries > 0 {
+    println!("Succeeded After Retry: {}", summary.succeeded_after_retry);
+    println!("Total Retries: {}", summary.retries);
+  }
+  if summary.delayed_launches > 0 {
+    println!("Delayed Launches (concurrency cap hit): {}", summary.delayed_launches);
+  }
+  if summary.skipped_launches > 0 {
+    println!("Skipped Launches (concurrency cap hit): {}", summary.skipped_launches);
+  }
 
-  Ok(())
+  print_duration_stats("Successful Tasks Statistics", &summary.successful_durations);
+  print_duration_stats("Failed Tasks Statistics", &summary.failed_durations);
+  print_outcomes(summary);
+  print_usage(summary);
+  print_phases(summary);
+
+  println!(
+    "\nTotal command-pool execution time: {}",
+    format_duration_custom(summary.total_duration)
+  );
 }
@@ -0,0 +1,487 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tokio::sync::{Notify, mpsc};
+use tokio::task::{JoinError, JoinSet};
+use tokio::time::{self, Duration, Instant};
+
+use crate::config::PoolConfig;
+use crate::process::ProcessGroups;
+use crate::rate::Saturation;
+use crate::summary::{PhaseSummary, PoolSummary};
+use crate::task::{self, OutputStream, TaskOutcome, TaskResult, TaskSpec};
+use crate::threshold::{FailureThresholds, FailureTracker, StopMode};
+
+/// How often the runner re-evaluates the concurrency of a load profile and the deadline while no task finishes.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Progress notifications of a running pool.
+#[derive(Debug, Clone)]
+pub enum PoolEvent {
+  /// A task started, `running` includes it.
+  TaskStarted { task: TaskSpec, running: usize },
+  /// A task printed a line, only sent when `stream_output` is set. The line ending is stripped.
+  TaskOutput {
+    task_id: usize,
+    stream: OutputStream,
+    line: String,
+  },
+  /// An attempt of a task failed, it is retried after `delay`.
+  TaskRetrying { result: TaskResult, delay: Duration },
+  /// A task finished, `running` no longer includes it.
+  TaskFinished { result: TaskResult, running: usize },
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct EventSender(Option<mpsc::UnboundedSender<PoolEvent>>);
+
+impl EventSender {
+  pub(crate) fn send(&self, event: PoolEvent) {
+    if let Some(tx) = &self.0 {
+      // The receiver may have been dropped, the pool keeps running regardless
+      let _ = tx.send(event);
+    }
+  }
+}
+
+/// State shared between the runner, its tasks and the handles.
+#[derive(Default)]
+struct Shared {
+  running: AtomicUsize,
+  stopping: AtomicBool,
+  paused: AtomicBool,
+  /// Current concurrency limit, followed by the runner when set through a handle or without a load profile.
+  concurrency: AtomicUsize,
+  /// The concurrency was set through a handle and no longer follows the load profile.
+  manual_concurrency: AtomicBool,
+  /// Tasks cancelled through a handle, they are not retried.
+  cancelled: Mutex<HashSet<usize>>,
+  /// Wakes the runner up when a handle changed the pause or the concurrency.
+  wake: Notify,
+  groups: ProcessGroups,
+  /// Statistics of the tasks finished so far, kept here so that handles can take snapshots.
+  summary: Mutex<PoolSummary>,
+  start_time: OnceLock<Instant>,
+}
+
+/// Controls a pool while it runs, e.g. from a signal handler.
+#[derive(Clone)]
+pub struct PoolHandle {
+  shared: Arc<Shared>,
+}
+
+impl PoolHandle {
+  /// Stops launching new tasks and sends `signal` to the process groups of the running tasks.
+  ///
+  /// The run ends once the running tasks have finished.
+  pub fn interrupt(&self, signal: i32) {
+    self.shared.stopping.store(true, Ordering::SeqCst);
+    self.shared.groups.broadcast(signal);
+  }
+
+  /// Stops launching new tasks, the run ends once the running tasks have finished.
+  pub fn stop(&self) {
+    self.shared.stopping.store(true, Ordering::SeqCst);
+    self.shared.wake.notify_one();
+  }
+
+  /// Stops launching new tasks and kills the process groups of the running tasks.
+  pub fn kill(&self) {
+    self.interrupt(libc::SIGKILL);
+  }
+
+  /// Stops launching new tasks until [`PoolHandle::resume`] is called, the running tasks keep going.
+  pub fn pause(&self) {
+    self.shared.paused.store(true, Ordering::SeqCst);
+    self.shared.wake.notify_one();
+  }
+
+  pub fn resume(&self) {
+    self.shared.paused.store(false, Ordering::SeqCst);
+    self.shared.wake.notify_one();
+  }
+
+  pub fn is_paused(&self) -> bool {
+    self.shared.paused.load(Ordering::SeqCst)
+  }
+
+  /// Number of tasks running right now.
+  pub fn running(&self) -> usize {
+    self.shared.running.load(Ordering::SeqCst)
+  }
+
+  /// Statistics of the tasks finished so far, `total_duration` being the time elapsed since the start of the run.
+  pub fn snapshot(&self) -> PoolSummary {
+    let mut summary = self.shared.summary.lock().unwrap().clone();
+    summary.interrupted = self.shared.stopping.load(Ordering::SeqCst);
+    summary.total_duration = self.shared.start_time.get().map(Instant::elapsed).unwrap_or_default();
+    summary
+  }
+
+  /// Maximum number of tasks running at once at this point of the run.
+  pub fn concurrency(&self) -> usize {
+    self.shared.concurrency.load(Ordering::SeqCst)
+  }
+
+  /// Changes the maximum number of tasks running at once, for the rest of the run even if it follows a load profile.
+  ///
+  /// When lowered, the running tasks are left to finish and no task is launched until fewer are running.
+  pub fn set_concurrency(&self, concurrency: usize) {
+    self.shared.concurrency.store(concurrency, Ordering::SeqCst);
+    self.shared.manual_concurrency.store(true, Ordering::SeqCst);
+    self.shared.wake.notify_one();
+  }
+
+  /// Kills the process group of a running task, which then fails without being retried.
+  ///
+  /// Returns false if the task is not running.
+  pub fn cancel(&self, task_id: usize) -> bool {
+    self.shared.cancelled.lock().unwrap().insert(task_id);
+    let signalled = self.shared.groups.signal(task_id, libc::SIGKILL);
+    if !signalled {
+      self.shared.cancelled.lock().unwrap().remove(&task_id);
+    }
+    signalled
+  }
+}
+
+/// Runs the tasks described by a [`PoolConfig`].
+pub struct Pool {
+  config: PoolConfig,
+  events: EventSender,
+  shared: Arc<Shared>,
+}
+
+impl Pool {
+  pub fn new(config: PoolConfig) -> Self {
+    let shared = Shared {
+      concurrency: AtomicUsize::new(config.concurrency),
+      ..Shared::default()
+    };
+    Pool {
+      config,
+      events: EventSender::default(),
+      shared: Arc::new(shared),
+    }
+  }
+
+  /// Returns a handle to control the run.
+  pub fn handle(&self) -> PoolHandle {
+    PoolHandle {
+      shared: Arc::clone(&self.shared),
+    }
+  }
+
+  /// Subscribes to the events of the run, the stream ends when the run is over.
+  ///
+  /// Only the receiver returned by the last call gets events.
+  pub fn events(&mut self) -> mpsc::UnboundedReceiver<PoolEvent> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    self.events = EventSender(Some(tx));
+    rx
+  }
+
+  /// Runs all tasks and returns the statistics of the run.
+  ///
+  /// Fails only if a task panicked.
+  pub async fn run(self) -> Result<PoolSummary, JoinError> {
+    let start_time = Instant::now();
+    let _ = self.shared.start_time.set(start_time);
+    let remaining = self.config.tasks_to_run();
+    *self.shared.summary.lock().unwrap() = PoolSummary {
+      total_tasks: remaining,
+      phases: self
+        .config
+        .profile
+        .iter()
+        .flat_map(|profile| profile.phases())
+        .copied()
+        .map(PhaseSummary::new)
+        .collect(),
+      ..PoolSummary::default()
+    };
+    let profile_end = self.config.profile.as_ref().map(|profile| start_time + profile.duration());
+    let slots = match &self.config.profile {
+      Some(profile) => profile.peak(),
+      None => self.config.concurrency,
+    };
+    let mut thresholds = self.config.failure_thresholds.clone();
+    if self.config.stop_on_fail {
+      thresholds.max_failures = Some(1);
+    }
+    let mut runner = Runner {
+      deadline: self
+        .config
+        .duration
+        .map(|duration| start_time + duration)
+        .into_iter()
+        .chain(profile_end)
+        .min(),
+      next_arrival: self.config.rate.map(|_| start_time),
+      free_slots: (1..=slots).rev().collect(),
+      slots,
+      start_time,
+      started_at: SystemTime::now(),
+      config: Arc::new(self.config),
+      events: self.events,
+      shared: self.shared,
+      join_set: JoinSet::new(),
+      launched: 0,
+      remaining,
+      pending: 0,
+      thresholds,
+      failures: FailureTracker::default(),
+      halted: false,
+    };
+
+    // Closed loop: fill the pool up to the concurrency limit, staggering the launches
+    if runner.next_arrival.is_none() {
+      for idx in 0..runner.concurrency() {
+        if !runner.can_launch() {
+          break;
+        }
+        let delay = runner.config.launch_delay * idx as u32;
+        runner.launch(delay);
+      }
+    }
+
+    // Continuously spawn new tasks as old ones complete, or as they arrive at the configured rate,
+    // until total_tasks is reached or the duration is over
+    let mut tick = time::interval(TICK);
+    tick.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    loop {
+      let arrival = runner.next_arrival.filter(|_| runner.can_launch());
+      let exhausted = runner.exhausted();
+      // Launches waiting for a slot, e.g. while paused, still have to run unless the run stopped
+      let waiting = runner.pending > 0 && !runner.stopped();
+      if runner.join_set.is_empty() && exhausted && !waiting {
+        break;
+      }
+      tokio::select! {
+        Some(res) = runner.join_set.join_next() => {
+          let (slot, result) = res?;
+          if !runner.finish(slot, result) {
+            break;
+          }
+        }
+        _ = time::sleep_until(arrival.unwrap_or_else(Instant::now)), if arrival.is_some() => runner.arrive(),
+        _ = tick.tick(), if !exhausted || waiting => runner.fill(),
+        _ = runner.shared.wake.notified() => runner.wake(),
+      }
+    }
+
+    runner.join_set.abort_all();
+    let mut summary = std::mem::take(&mut *runner.shared.summary.lock().unwrap());
+    summary.interrupted = runner.shared.stopping.load(Ordering::SeqCst);
+    summary.total_duration = start_time.elapsed();
+    Ok(summary)
+  }
+}
+
+struct Runner {
+  config: Arc<PoolConfig>,
+  events: EventSender,
+  shared: Arc<Shared>,
+  join_set: JoinSet<(usize, Option<TaskResult>)>,
+  start_time: Instant,
+  /// Wall-clock start of the run, to find the profile phase of a task from its start time.
+  started_at: SystemTime,
+  /// Slots not taken by a running task, the lowest one on top.
+  free_slots: Vec<usize>,
+  /// Number of slots handed out so far, a new one is added when the concurrency grows past it.
+  slots: usize,
+  /// Id of the last launched task.
+  launched: usize,
+  /// Number of tasks left to launch, `None` for unbounded runs.
+  remaining: Option<usize>,
+  /// Open-loop launches waiting for a free slot.
+  pending: usize,
+  /// Time of the next open-loop launch, `None` in closed loop.
+  next_arrival: Option<Instant>,
+  deadline: Option<Instant>,
+  /// Failure thresholds of the run, `stop_on_fail` included.
+  thresholds: FailureThresholds,
+  failures: FailureTracker,
+  /// A failure threshold was crossed in drain mode, the running tasks are left to finish.
+  halted: bool,
+}
+
+impl Runner {
+  /// Maximum number of tasks running at this point of the run.
+  fn concurrency(&self) -> usize {
+    match &self.config.profile {
+      Some(profile) if !self.shared.manual_concurrency.load(Ordering::SeqCst) => {
+        let concurrency = profile.concurrency_at(self.start_time.elapsed());
+        self.shared.concurrency.store(concurrency, Ordering::SeqCst);
+        concurrency
+      }
+      _ => self.shared.concurrency.load(Ordering::SeqCst),
+    }
+  }
+
+  fn has_capacity(&self) -> bool {
+    self.join_set.len() < self.concurrency()
+  }
+
+  /// No more task will arrive: the run stopped, or every remaining task already arrived and waits in `pending`.
+  fn exhausted(&self) -> bool {
+    self.stopped() || self.remaining.is_some_and(|remaining| remaining <= self.pending)
+  }
+
+  /// No more task will be launched, not even the pending ones: the run is stopping or the deadline is over.
+  fn stopped(&self) -> bool {
+    self.halted || self.shared.stopping.load(Ordering::SeqCst) || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+  }
+
+  fn can_launch(&self) -> bool {
+    !self.shared.paused.load(Ordering::SeqCst) && !self.exhausted()
+  }
+
+  /// Handles a finished task, returns false if the run must stop.
+  fn finish(&mut self, slot: usize, result: Option<TaskResult>) -> bool {
+    let idx = self.free_slots.partition_point(|&free| free > slot);
+    self.free_slots.insert(idx, slot);
+    // Tasks interrupted before they started have no result
+    if let Some(result) = result {
+      let running = self.shared.running.fetch_sub(1, Ordering::SeqCst) - 1;
+      {
+        let mut summary = self.shared.summary.lock().unwrap();
+        summary.record(&result);
+        if let Some(profile) = &self.config.profile
+          && let Ok(elapsed) = result.started_at.duration_since(self.started_at)
+          && let Some(phase) = summary.phases.get_mut(profile.phase_at(elapsed))
+        {
+          phase.record(&result);
+        }
+      }
+      let failed = !result.outcome.is_success();
+      self.events.send(PoolEvent::TaskFinished { result, running });
+
+      if let Some(reason) = self.failures.record(&self.thresholds, failed)
+        && !self.halted
+      {
+        let mut summary = self.shared.summary.lock().unwrap();
+        summary.stopped_early = true;
+        summary.stop_reason = Some(reason);
+        match self.config.stop_mode {
+          StopMode::Drain => self.halted = true,
+          StopMode::Abort => {
+            self.shared.groups.broadcast(libc::SIGKILL);
+            return false;
+          }
+        }
+      }
+    }
+
+    self.fill();
+    true
+  }
+
+  /// Launches the delayed open-loop tasks, or in closed loop new tasks, while the concurrency allows it.
+  fn fill(&mut self) {
+    while self.has_capacity() && !self.shared.paused.load(Ordering::SeqCst) {
+      if self.pending > 0 {
+        self.pending -= 1;
+        if self.stopped() {
+          // Stopped or past the deadline, drop the remaining delayed launches
+          self.pending = 0;
+          return;
+        }
+      } else if self.next_arrival.is_some() || !self.can_launch() {
+        return;
+      }
+      self.launch(Duration::ZERO);
+    }
+  }
+
+  /// Applies a pause or concurrency change made through a handle.
+  fn wake(&mut self) {
+    // Arrivals missed while paused are not caught up
+    let now = Instant::now();
+    if let Some(next_arrival) = self.next_arrival
+      && next_arrival < now
+    {
+      self.next_arrival = Some(now);
+    }
+    self.fill();
+  }
+
+  /// Launches the task arriving now in an open-loop run, and schedules the next arrival.
+  fn arrive(&mut self) {
+    if let (Some(next_arrival), Some(rate)) = (self.next_arrival, self.config.rate) {
+      self.next_arrival = Some(next_arrival + self.config.arrivals.next_interval(rate));
+    }
+    if self.has_capacity() {
+      self.launch(Duration::ZERO);
+      return;
+    }
+    let mut summary = self.shared.summary.lock().unwrap();
+    match self.config.saturation {
+      Saturation::Delay => {
+        self.pending += 1;
+        summary.delayed_launches += 1;
+      }
+      Saturation::Skip => summary.skipped_launches += 1,
+    }
+  }
+
+  /// Spawns the next task into the lowest free slot, starting it after `delay`.
+  fn launch(&mut self, delay: Duration) {
+    let slot = match self.free_slots.pop() {
+      Some(slot) => slot,
+      None => {
+        self.slots += 1;
+        self.slots
+      }
+    };
+    self.launched += 1;
+    while self.config.skip_tasks.contains(&self.launched) {
+      self.launched += 1;
+    }
+    if let Some(remaining) = &mut self.remaining {
+      *remaining -= 1;
+    }
+    let input = self.config.inputs.as_ref().and_then(|inputs| inputs.get(self.launched - 1));
+    let spec = TaskSpec::from_template(&self.config.command, input.map(String::as_str), self.launched, slot);
+    let config = Arc::clone(&self.config);
+    let events = self.events.clone();
+    let shared = Arc::clone(&self.shared);
+
+    self.join_set.spawn(async move {
+      if !delay.is_zero() {
+        time::sleep(delay).await;
+        if shared.stopping.load(Ordering::SeqCst) {
+          return (slot, None);
+        }
+      }
+      let running = shared.running.fetch_add(1, Ordering::SeqCst) + 1;
+      events.send(PoolEvent::TaskStarted {
+        task: spec.clone(),
+        running,
+      });
+      let mut attempt = 1;
+      loop {
+        let mut result = task::execute(spec.clone(), attempt, &config, &shared.groups, &events).await;
+        let stopping = shared.stopping.load(Ordering::SeqCst);
+        let cancelled = shared.cancelled.lock().unwrap().contains(&spec.id);
+        if cancelled && !result.outcome.is_success() {
+          result.outcome = TaskOutcome::Cancelled;
+        }
+        if result.outcome.is_success() || attempt > config.retries || stopping || cancelled {
+          return (slot, Some(result));
+        }
+        let delay = config.backoff.delay(config.retry_delay, attempt);
+        events.send(PoolEvent::TaskRetrying {
+          result: result.clone(),
+          delay,
+        });
+        time::sleep(delay).await;
+        if shared.stopping.load(Ordering::SeqCst) {
+          return (slot, Some(result));
+        }
+        attempt += 1;
+      }
+    });
+  }
+}
@@ -0,0 +1,242 @@
+//! Child process handling. Every task runs in its own process group, so that signals reach the whole process tree.
+
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::pin::Pin;
+use std::process::{Command, ExitStatus};
+use std::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{ChildStderr, ChildStdout};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::time::{self, Duration, Instant};
+
+use crate::task::Termination;
+
+/// Sends `signal` to every process of the group, returns false if no process was signalled.
+pub(crate) fn signal_group(pgid: u32, signal: libc::c_int) -> bool {
+  // SAFETY: kill has no memory safety requirements, a negative pid targets the process group
+  unsafe { libc::kill(-(pgid as libc::pid_t), signal) == 0 }
+}
+
+/// Process groups of the running tasks, keyed by task id, so that signals can be forwarded to them.
+#[derive(Default)]
+pub(crate) struct ProcessGroups(Mutex<GroupsState>);
+
+#[derive(Default)]
+struct GroupsState {
+  groups: HashMap<usize, u32>,
+  /// Last broadcast signal, also delivered to groups registered afterwards.
+  forwarded: Option<libc::c_int>,
+}
+
+impl ProcessGroups {
+  pub(crate) fn register(&self, task_id: usize, pgid: u32) {
+    let mut state = self.0.lock().unwrap();
+    state.groups.insert(task_id, pgid);
+    if let Some(signal) = state.forwarded {
+      signal_group(pgid, signal);
+    }
+  }
+
+  pub(crate) fn unregister(&self, task_id: usize) {
+    self.0.lock().unwrap().groups.remove(&task_id);
+  }
+
+  /// Sends `signal` to the group of a task, returns false if the task is not running.
+  pub(crate) fn signal(&self, task_id: usize, signal: libc::c_int) -> bool {
+    let state = self.0.lock().unwrap();
+    state.groups.get(&task_id).is_some_and(|&pgid| signal_group(pgid, signal))
+  }
+
+  /// Sends `signal` to every tracked group, and to the groups registered later on.
+  pub(crate) fn broadcast(&self, signal: libc::c_int) {
+    let mut state = self.0.lock().unwrap();
+    state.forwarded = Some(signal);
+    for &pgid in state.groups.values() {
+      signal_group(pgid, signal);
+    }
+  }
+}
+
+/// Resource usage of a task process, as reported by `wait4` when it is reaped.
+///
+/// Covers the process and the descendants it waited for, `max_rss` being the largest of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+  /// Peak resident set size in bytes.
+  pub max_rss: u64,
+  /// CPU time spent in user mode.
+  pub user_time: Duration,
+  /// CPU time spent in the kernel.
+  pub system_time: Duration,
+  /// Times the process gave up the CPU, typically to wait for I/O.
+  pub voluntary_context_switches: u64,
+  /// Times the process was preempted, a sign of CPU contention.
+  pub involuntary_context_switches: u64,
+}
+
+impl ResourceUsage {
+  fn from_rusage(usage: &libc::rusage) -> Self {
+    // ru_maxrss is in bytes on macOS, in kilobytes everywhere else
+    let max_rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+    ResourceUsage {
+      max_rss: usage.ru_maxrss as u64 * max_rss_unit,
+      user_time: duration_from_timeval(usage.ru_utime),
+      system_time: duration_from_timeval(usage.ru_stime),
+      voluntary_context_switches: usage.ru_nvcsw as u64,
+      involuntary_context_switches: usage.ru_nivcsw as u64,
+    }
+  }
+}
+
+fn duration_from_timeval(time: libc::timeval) -> Duration {
+  Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000)
+}
+
+/// The leader process of a task, spawned with std and reaped with `wait4` so that its resource usage is known.
+///
+/// It is the only owner of the pid: tokio never sees the process, so nothing else can wait on the pid once it is reused.
+/// Its process group is killed if it is dropped before the leader was reaped, e.g. when the run is aborted.
+pub(crate) struct TaskProcess {
+  pid: u32,
+  reaped: bool,
+}
+
+impl TaskProcess {
+  /// Spawns `cmd` in its own process group, with its stdout and stderr piped for tokio to read.
+  pub(crate) fn spawn(cmd: &mut Command) -> io::Result<(Self, Option<ChildStdout>, Option<ChildStderr>)> {
+    let mut child = cmd.process_group(0).spawn()?;
+    let process = TaskProcess {
+      pid: child.id(),
+      reaped: false,
+    };
+    // Dropping the std child neither waits on nor kills the process, `process` owns it from here
+    let stdout = child.stdout.take().map(ChildStdout::from_std).transpose()?;
+    let stderr = child.stderr.take().map(ChildStderr::from_std).transpose()?;
+    Ok((process, stdout, stderr))
+  }
+
+  /// Id of the process group, the same as the pid of its leader.
+  pub(crate) fn pgid(&self) -> u32 {
+    self.pid
+  }
+
+  /// Waits for the leader to exit and reaps it.
+  ///
+  /// Like tokio does for its own children, the exit is noticed on SIGCHLD, so no thread is blocked per task.
+  pub(crate) async fn wait(&mut self) -> io::Result<(ExitStatus, ResourceUsage)> {
+    // Listen before the first check, so that an exit in between is not missed
+    let mut sigchld = signal(SignalKind::child())?;
+    loop {
+      if let Some(exited) = self.try_wait()? {
+        return Ok(exited);
+      }
+      sigchld.recv().await;
+    }
+  }
+
+  fn try_wait(&mut self) -> io::Result<Option<(ExitStatus, ResourceUsage)>> {
+    let mut status = 0;
+    // SAFETY: rusage is plain old data, all zeroes is a valid value
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: status and usage are valid for writes for the duration of the call
+    let reaped = unsafe { libc::wait4(self.pid as libc::pid_t, &mut status, libc::WNOHANG, &mut usage) };
+    match reaped {
+      -1 => Err(io::Error::last_os_error()),
+      0 => Ok(None),
+      _ => {
+        self.reaped = true;
+        Ok(Some((ExitStatus::from_raw(status), ResourceUsage::from_rusage(&usage))))
+      }
+    }
+  }
+}
+
+impl Drop for TaskProcess {
+  fn drop(&mut self) {
+    // Until the leader is reaped its pid cannot be reused, so the group is still the task's
+    if self.reaped {
+      return;
+    }
+    signal_group(self.pid, libc::SIGKILL);
+    // Nobody else reaps the leader, wait for it off the runtime, SIGKILL makes that quick
+    let pid = self.pid as libc::pid_t;
+    std::thread::spawn(move || {
+      let mut status = 0;
+      // SAFETY: status is valid for writes for the duration of the call
+      unsafe { libc::waitpid(pid, &mut status, 0) };
+    });
+  }
+}
+
+/// How long the pipes of a killed task are still read, in case a process outside its group holds them open.
+const PIPE_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The end of a task: its leader exiting and its output pipes closing, which descendants may delay past the exit.
+pub(crate) struct Completion<'a, P> {
+  process: &'a mut TaskProcess,
+  /// Reads both pipes to their end.
+  pipes: Pin<&'a mut P>,
+  pipes_closed: bool,
+  /// Result of reaping the leader, once it exited.
+  pub(crate) exited: Option<io::Result<(ExitStatus, ResourceUsage)>>,
+}
+
+impl<'a, P: Future> Completion<'a, P> {
+  pub(crate) fn new(process: &'a mut TaskProcess, pipes: Pin<&'a mut P>) -> Self {
+    Completion {
+      process,
+      pipes,
+      pipes_closed: false,
+      exited: None,
+    }
+  }
+
+  /// Waits for the leader to exit and the pipes to close, returns false if `deadline` passed first.
+  pub(crate) async fn wait_until(&mut self, deadline: Option<Instant>) -> bool {
+    while self.exited.is_none() || !self.pipes_closed {
+      tokio::select! {
+        exited = self.process.wait(), if self.exited.is_none() => self.exited = Some(exited),
+        _ = self.pipes.as_mut(), if !self.pipes_closed => self.pipes_closed = true,
+        _ = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => return false,
+      }
+    }
+    true
+  }
+
+  /// Stops the process group: SIGTERM first, then SIGKILL once the grace period is over, and reads what is left of the pipes.
+  pub(crate) async fn terminate(&mut self, grace: Duration) -> Termination {
+    let pgid = self.process.pgid();
+    signal_group(pgid, libc::SIGTERM);
+    let exited = self.wait_until(Some(Instant::now() + grace)).await;
+    // Descendants may outlive the group leader, kill whatever is left of the group
+    let killed = signal_group(pgid, libc::SIGKILL);
+    self.wait_until(Some(Instant::now() + PIPE_DRAIN_TIMEOUT)).await;
+    if self.exited.is_none() {
+      self.exited = Some(self.process.wait().await);
+    }
+    if exited && !killed {
+      Termination::Terminated
+    } else {
+      Termination::Killed
+    }
+  }
+}
+
+/// Reads a piped output stream to its end into `buf`, handing every line to `on_line` as soon as it arrives.
+///
+/// Keeps what was read if the stream fails or the read is dropped.
+pub(crate) async fn read_pipe<R: AsyncRead + Unpin>(pipe: Option<R>, buf: &mut Vec<u8>, mut on_line: impl FnMut(&[u8])) {
+  let Some(pipe) = pipe else {
+    return;
+  };
+  let mut reader = BufReader::new(pipe);
+  loop {
+    let line_start = buf.len();
+    match reader.read_until(b'\n', buf).await {
+      Ok(0) | Err(_) => break,
+      Ok(_) => on_line(&buf[line_start..]),
+    }
+  }
+}
@@ -0,0 +1,275 @@
+//! Load profiles that change the effective concurrency over time, e.g. `ramp 0->32 over 2m, hold 10m, ramp 32->0 over 1m`.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// One step of a [`LoadProfile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+  /// Linearly move the concurrency from `from` to `to`.
+  Ramp { from: usize, to: usize, over: Duration },
+  /// Keep the concurrency at `level`.
+  Hold { level: usize, duration: Duration },
+}
+
+impl Phase {
+  pub fn duration(&self) -> Duration {
+    match *self {
+      Phase::Ramp { over, .. } => over,
+      Phase::Hold { duration, .. } => duration,
+    }
+  }
+
+  fn end_level(&self) -> usize {
+    match *self {
+      Phase::Ramp { to, .. } => to,
+      Phase::Hold { level, .. } => level,
+    }
+  }
+
+  fn level_at(&self, elapsed: Duration) -> usize {
+    match *self {
+      Phase::Ramp { from, to, over } => {
+        let progress = if over.is_zero() {
+          1.0
+        } else {
+          (elapsed.as_secs_f64() / over.as_secs_f64()).min(1.0)
+        };
+        (from as f64 + (to as f64 - from as f64) * progress).round() as usize
+      }
+      Phase::Hold { level, .. } => level,
+    }
+  }
+}
+
+impl fmt::Display for Phase {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match *self {
+      Phase::Ramp { from, to, over } => write!(f, "ramp {from}->{to} over {}", humantime::format_duration(over)),
+      Phase::Hold { level, duration } => write!(f, "hold {level} for {}", humantime::format_duration(duration)),
+    }
+  }
+}
+
+/// Sequence of phases driving the concurrency of a run, the run ends with the last phase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadProfile {
+  phases: Vec<Phase>,
+}
+
+impl LoadProfile {
+  pub fn phases(&self) -> &[Phase] {
+    &self.phases
+  }
+
+  /// Total length of the profile.
+  pub fn duration(&self) -> Duration {
+    self.phases.iter().map(Phase::duration).sum()
+  }
+
+  /// Highest concurrency the profile reaches.
+  pub fn peak(&self) -> usize {
+    self
+      .phases
+      .iter()
+      .map(|phase| match *phase {
+        Phase::Ramp { from, to, .. } => from.max(to),
+        Phase::Hold { level, .. } => level,
+      })
+      .max()
+      .unwrap_or_default()
+  }
+
+  /// Index of the phase running `elapsed` after the start, the last phase past the end.
+  pub fn phase_at(&self, elapsed: Duration) -> usize {
+    let mut phase_start = Duration::ZERO;
+    for (idx, phase) in self.phases.iter().enumerate() {
+      phase_start += phase.duration();
+      if elapsed < phase_start {
+        return idx;
+      }
+    }
+    self.phases.len().saturating_sub(1)
+  }
+
+  /// Concurrency `elapsed` after the start.
+  pub fn concurrency_at(&self, elapsed: Duration) -> usize {
+    let mut phase_start = Duration::ZERO;
+    for phase in &self.phases {
+      if elapsed < phase_start + phase.duration() {
+        return phase.level_at(elapsed - phase_start);
+      }
+      phase_start += phase.duration();
+    }
+    self.phases.last().map_or(0, Phase::end_level)
+  }
+}
+
+impl FromStr for LoadProfile {
+  type Err = String;
+
+  /// Parses comma separated phases: `ramp A->B over DURATION`, `hold N for DURATION` or `hold DURATION`,
+  /// the latter keeping the level the previous phase ended at.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut phases: Vec<Phase> = Vec::new();
+    for spec in s.split(',').map(str::trim) {
+      let words: Vec<&str> = spec.split_whitespace().collect();
+      let phase = match words.as_slice() {
+        ["ramp", levels, "over", over @ ..] => {
+          let (from, to) = levels
+            .split_once("->")
+            .ok_or_else(|| format!("invalid ramp `{spec}`, expected `ramp A->B over DURATION`"))?;
+          Phase::Ramp {
+            from: parse_level(from)?,
+            to: parse_level(to)?,
+            over: parse_duration(&over.join(" "))?,
+          }
+        }
+        ["hold", level, "for", duration @ ..] => Phase::Hold {
+          level: parse_level(level)?,
+          duration: parse_duration(&duration.join(" "))?,
+        },
+        ["hold", duration @ ..] => {
+          let level = phases
+            .last()
+            .map(Phase::end_level)
+            .ok_or_else(|| format!("`{spec}` needs a previous phase, use `hold N for DURATION`"))?;
+          Phase::Hold {
+            level,
+            duration: parse_duration(&duration.join(" "))?,
+          }
+        }
+        _ => {
+          return Err(format!(
+            "invalid phase `{spec}`, expected `ramp A->B over DURATION` or `hold [N for] DURATION`"
+          ));
+        }
+      };
+      phases.push(phase);
+    }
+    Ok(LoadProfile { phases })
+  }
+}
+
+impl fmt::Display for LoadProfile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (idx, phase) in self.phases.iter().enumerate() {
+      if idx > 0 {
+        write!(f, ", ")?;
+      }
+      write!(f, "{phase}")?;
+    }
+    Ok(())
+  }
+}
+
+fn parse_level(level: &str) -> Result<usize, String> {
+  level.trim().parse().map_err(|_| format!("invalid concurrency `{level}`"))
+}
+
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+  humantime::parse_duration(duration).map_err(|e| format!("invalid duration `{duration}`: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SECOND: Duration = Duration::from_secs(1);
+
+  fn profile(spec: &str) -> LoadProfile {
+    spec.parse().unwrap()
+  }
+
+  #[test]
+  fn parses_ramps_and_holds() {
+    let profile = profile("ramp 0->32 over 2m, hold 10m, ramp 32->0 over 1m");
+    assert_eq!(
+      profile.phases(),
+      [
+        Phase::Ramp {
+          from: 0,
+          to: 32,
+          over: 120 * SECOND
+        },
+        Phase::Hold {
+          level: 32,
+          duration: 600 * SECOND
+        },
+        Phase::Ramp {
+          from: 32,
+          to: 0,
+          over: 60 * SECOND
+        },
+      ]
+    );
+    assert_eq!(profile.duration(), 780 * SECOND);
+    assert_eq!(profile.peak(), 32);
+  }
+
+  #[test]
+  fn hold_without_level_keeps_the_previous_level() {
+    let profile = profile("hold 4 for 1m, ramp 4->16 over 1m, hold 1m 30s");
+    assert_eq!(
+      profile.phases()[2],
+      Phase::Hold {
+        level: 16,
+        duration: 90 * SECOND
+      }
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_phases() {
+    for spec in [
+      "hold 1m",
+      "ramp 0-8 over 1m",
+      "ramp 0->x over 1m",
+      "ramp 0->8 over",
+      "hold x for 1m",
+      "jump to 8",
+      "",
+    ] {
+      assert!(spec.parse::<LoadProfile>().is_err(), "`{spec}` should not parse");
+    }
+  }
+
+  #[test]
+  fn concurrency_follows_the_phases() {
+    let profile = profile("ramp 0->32 over 2m, hold 10m, ramp 32->0 over 1m");
+    assert_eq!(profile.concurrency_at(Duration::ZERO), 0);
+    assert_eq!(profile.concurrency_at(60 * SECOND), 16);
+    assert_eq!(profile.concurrency_at(120 * SECOND), 32);
+    assert_eq!(profile.concurrency_at(300 * SECOND), 32);
+    assert_eq!(profile.concurrency_at(750 * SECOND), 16);
+    assert_eq!(profile.phase_at(Duration::ZERO), 0);
+    assert_eq!(profile.phase_at(119 * SECOND), 0);
+    assert_eq!(profile.phase_at(120 * SECOND), 1);
+    assert_eq!(profile.phase_at(750 * SECOND), 2);
+  }
+
+  #[test]
+  fn zero_length_ramp_jumps_to_its_target() {
+    let profile = profile("ramp 0->8 over 0s, hold 1m");
+    assert_eq!(profile.duration(), 60 * SECOND);
+    assert_eq!(profile.concurrency_at(Duration::ZERO), 8);
+    assert_eq!(profile.phase_at(Duration::ZERO), 1);
+  }
+
+  #[test]
+  fn past_the_end_stays_at_the_last_level() {
+    let down = profile("ramp 0->32 over 2m, ramp 32->0 over 1m");
+    assert_eq!(down.concurrency_at(3600 * SECOND), 0);
+    assert_eq!(down.phase_at(3600 * SECOND), 1);
+    let hold = profile("hold 4 for 1m");
+    assert_eq!(hold.concurrency_at(3600 * SECOND), 4);
+    assert_eq!(hold.phase_at(3600 * SECOND), 0);
+  }
+
+  #[test]
+  fn display_round_trips() {
+    let original = profile("ramp 1->8 over 30s, hold 2m, hold 3 for 1m");
+    assert_eq!(original.to_string().parse::<LoadProfile>().unwrap(), original);
+  }
+}
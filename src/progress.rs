@@ -0,0 +1,130 @@
+//! Progress line redrawn in place at the bottom of the terminal, shown when stdout is a TTY.
+
+use command_pool::PoolEvent;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::format_duration_custom;
+
+/// Number of most recent task durations the rolling percentiles are computed from.
+const ROLLING_WINDOW: usize = 200;
+
+pub struct Progress {
+  total_tasks: Option<usize>,
+  /// Time after which no task is launched anymore, for duration based runs.
+  run_length: Option<Duration>,
+  start_time: Instant,
+  completed: usize,
+  running: usize,
+  succeeded: usize,
+  failed: usize,
+  recent_durations: VecDeque<Duration>,
+}
+
+impl Progress {
+  pub fn new(total_tasks: Option<usize>, run_length: Option<Duration>) -> Self {
+    Progress {
+      total_tasks,
+      run_length,
+      start_time: Instant::now(),
+      completed: 0,
+      running: 0,
+      succeeded: 0,
+      failed: 0,
+      recent_durations: VecDeque::with_capacity(ROLLING_WINDOW),
+    }
+  }
+
+  pub fn record(&mut self, event: &PoolEvent) {
+    match event {
+      PoolEvent::TaskStarted { running, .. } => self.running = *running,
+      PoolEvent::TaskFinished { result, running } => {
+        self.running = *running;
+        self.completed += 1;
+        if result.outcome.is_success() {
+          self.succeeded += 1;
+        } else {
+          self.failed += 1;
+        }
+        if self.recent_durations.len() == ROLLING_WINDOW {
+          self.recent_durations.pop_front();
+        }
+        self.recent_durations.push_back(result.duration);
+      }
+      PoolEvent::TaskOutput { .. } | PoolEvent::TaskRetrying { .. } => {}
+    }
+  }
+
+  /// Erases the progress line, so that regular output can be printed in its place.
+  pub fn clear(&self) {
+    print!("\r\x1b[2K");
+    let _ = std::io::stdout().flush();
+  }
+
+  /// Redraws the progress line, truncated to the terminal width so that it never wraps.
+  pub fn draw(&self) {
+    let mut line = self.line();
+    line.truncate(terminal_width().saturating_sub(1));
+    print!("\r\x1b[2K{line}");
+    let _ = std::io::stdout().flush();
+  }
+
+  fn line(&self) -> String {
+    let elapsed = self.start_time.elapsed();
+    let throughput = self.completed as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    let mut line = match self.total_tasks {
+      Some(total_tasks) => format!(
+        "Progress: {}/{total_tasks} ({:.1}%)",
+        self.completed,
+        self.completed as f64 / total_tasks.max(1) as f64 * 100.0
+      ),
+      None => format!("Progress: {}", self.completed),
+    };
+    line.push_str(&format!(
+      " | Running: {} | Succeeded: {} | Failed: {} | {throughput:.1} tasks/s",
+      self.running, self.succeeded, self.failed
+    ));
+    if let Some(eta) = self.eta(elapsed, throughput) {
+      line.push_str(&format!(" | ETA: {}", format_duration_custom(eta)));
+    }
+    if !self.recent_durations.is_empty() {
+      let mut durations: Vec<Duration> = self.recent_durations.iter().copied().collect();
+      durations.sort_unstable();
+      line.push_str(&format!(
+        " | p50: {} p95: {}",
+        format_duration_custom(percentile(&durations, 50.0)),
+        format_duration_custom(percentile(&durations, 95.0))
+      ));
+    }
+    line
+  }
+
+  /// Time left until the last task completes at the current throughput, or until the end of a duration based run.
+  fn eta(&self, elapsed: Duration, throughput: f64) -> Option<Duration> {
+    match (self.total_tasks, self.run_length) {
+      (Some(total_tasks), _) if throughput > 0.0 => {
+        let remaining = total_tasks.saturating_sub(self.completed);
+        Some(Duration::from_secs_f64(remaining as f64 / throughput))
+      }
+      (None, Some(run_length)) => Some(run_length.saturating_sub(elapsed)),
+      _ => None,
+    }
+  }
+}
+
+/// Nearest-rank percentile of sorted, non-empty durations.
+fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+  let rank = ((sorted.len() - 1) as f64 * percentile / 100.0).round() as usize;
+  sorted[rank]
+}
+
+fn terminal_width() -> usize {
+  // SAFETY: TIOCGWINSZ only writes into the winsize struct passed to it
+  let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+  if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+    size.ws_col as usize
+  } else {
+    80
+  }
+}
@@ -0,0 +1,161 @@
+//! Open-loop launching: tasks arrive at a given rate, whatever the completion speed.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Launch rate of an open-loop run, parsed from `50/s`, `300/m`, `10/h` or `50` (per second).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+  per_second: f64,
+}
+
+/// Slowest accepted rate, one launch a day.
+const MIN_PER_SECOND: f64 = 1.0 / 86_400.0;
+/// Longest interval between two launches, far above the mean interval of the slowest rate.
+const MAX_INTERVAL: Duration = Duration::from_secs(365 * 86_400);
+
+impl Rate {
+  /// Returns `None` unless the rate is finite and at least one launch a day.
+  pub fn per_second(per_second: f64) -> Option<Self> {
+    (per_second.is_finite() && per_second >= MIN_PER_SECOND).then_some(Rate { per_second })
+  }
+
+  pub fn as_per_second(self) -> f64 {
+    self.per_second
+  }
+}
+
+impl FromStr for Rate {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (count, unit_secs) = match s.split_once('/') {
+      Some((count, "s")) => (count, 1.0),
+      Some((count, "m")) => (count, 60.0),
+      Some((count, "h")) => (count, 3600.0),
+      Some((_, unit)) => return Err(format!("unknown rate unit `{unit}`, expected s, m or h")),
+      None => (s, 1.0),
+    };
+    match count.trim().parse::<f64>() {
+      Ok(count) if count > 0.0 && count.is_finite() => {
+        Rate::per_second(count / unit_secs).ok_or_else(|| format!("rate `{s}` is below one launch a day"))
+      }
+      _ => Err(format!("invalid rate `{s}`, expected a positive number like 50/s")),
+    }
+  }
+}
+
+impl fmt::Display for Rate {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}/s", self.per_second)
+  }
+}
+
+/// How the launches of an open-loop run are spread over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arrivals {
+  /// Launch at a fixed interval.
+  #[default]
+  Uniform,
+  /// Launch at exponentially distributed intervals, like independent clients would.
+  Poisson,
+}
+
+impl Arrivals {
+  /// Time until the next launch.
+  pub fn next_interval(self, rate: Rate) -> Duration {
+    let mean_secs = 1.0 / rate.per_second;
+    let secs = match self {
+      Arrivals::Uniform => mean_secs,
+      // Inverse transform sampling, 1 - f64() is in (0, 1] so ln() stays finite
+      Arrivals::Poisson => -(1.0 - fastrand::f64()).ln() * mean_secs,
+    };
+    Duration::try_from_secs_f64(secs).map_or(MAX_INTERVAL, |interval| interval.min(MAX_INTERVAL))
+  }
+}
+
+impl FromStr for Arrivals {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "uniform" | "fixed" => Ok(Arrivals::Uniform),
+      "poisson" => Ok(Arrivals::Poisson),
+      other => Err(format!("unknown arrival distribution `{other}`, expected uniform or poisson")),
+    }
+  }
+}
+
+impl fmt::Display for Arrivals {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Arrivals::Uniform => write!(f, "uniform"),
+      Arrivals::Poisson => write!(f, "poisson"),
+    }
+  }
+}
+
+/// What happens to a launch that arrives while `concurrency` tasks are already running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Saturation {
+  /// Launch it as soon as a task finishes.
+  #[default]
+  Delay,
+  /// Drop it.
+  Skip,
+}
+
+impl FromStr for Saturation {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "delay" => Ok(Saturation::Delay),
+      "skip" => Ok(Saturation::Skip),
+      other => Err(format!("unknown saturation policy `{other}`, expected delay or skip")),
+    }
+  }
+}
+
+impl fmt::Display for Saturation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Saturation::Delay => write!(f, "delay"),
+      Saturation::Skip => write!(f, "skip"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_rates() {
+    assert_eq!("50/s".parse(), Ok(Rate::per_second(50.0).unwrap()));
+    assert_eq!("300/m".parse(), Ok(Rate::per_second(5.0).unwrap()));
+    assert_eq!("36/h".parse(), Ok(Rate::per_second(0.01).unwrap()));
+    assert_eq!("2.5".parse(), Ok(Rate::per_second(2.5).unwrap()));
+    for spec in ["0/s", "-1/s", "NaN", "inf/s", "1e-300/s", "10/d", "fast"] {
+      assert!(spec.parse::<Rate>().is_err(), "`{spec}` should not parse");
+    }
+  }
+
+  #[test]
+  fn rejects_rates_without_a_finite_interval() {
+    for per_second in [0.0, -1.0, 1e-300, f64::NAN, f64::INFINITY] {
+      assert_eq!(Rate::per_second(per_second), None, "{per_second}/s should be rejected");
+    }
+  }
+
+  #[test]
+  fn intervals_follow_the_rate() {
+    let rate = Rate::per_second(4.0).unwrap();
+    assert_eq!(Arrivals::Uniform.next_interval(rate), Duration::from_millis(250));
+    let slowest = Rate::per_second(MIN_PER_SECOND).unwrap();
+    for _ in 0..1000 {
+      assert!(Arrivals::Poisson.next_interval(slowest) <= MAX_INTERVAL);
+    }
+  }
+}
@@ -0,0 +1,250 @@
+use hdrhistogram::Histogram;
+use std::time::Duration;
+
+use crate::process::ResourceUsage;
+
+/// Distribution of task durations, kept in an HDR histogram so memory stays bounded however many tasks run.
+///
+/// Durations are recorded in microseconds with 3 significant digits.
+#[derive(Debug, Clone)]
+pub struct DurationStats {
+  histogram: Histogram<u64>,
+}
+
+impl Default for DurationStats {
+  fn default() -> Self {
+    DurationStats {
+      histogram: Histogram::new(3).expect("3 significant digits are supported"),
+    }
+  }
+}
+
+impl DurationStats {
+  pub fn record(&mut self, duration: Duration) {
+    record(&mut self.histogram, u64::try_from(duration.as_micros()).unwrap_or(u64::MAX));
+  }
+
+  pub fn count(&self) -> u64 {
+    self.histogram.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.count() == 0
+  }
+
+  pub fn min(&self) -> Duration {
+    Duration::from_micros(self.histogram.min())
+  }
+
+  pub fn max(&self) -> Duration {
+    Duration::from_micros(self.histogram.max())
+  }
+
+  pub fn mean(&self) -> Duration {
+    Duration::from_secs_f64(self.histogram.mean() / 1e6)
+  }
+
+  pub fn stdev(&self) -> Duration {
+    Duration::from_secs_f64(self.histogram.stdev() / 1e6)
+  }
+
+  /// Duration below which `percentile` percent of the tasks finished, e.g. `percentile(99.9)`.
+  pub fn percentile(&self, percentile: f64) -> Duration {
+    Duration::from_micros(self.histogram.value_at_quantile(percentile / 100.0))
+  }
+
+  /// Splits the range between the min and the max into `count` equal buckets.
+  ///
+  /// Returns the upper bound of every bucket with the number of tasks that fell in it.
+  pub fn buckets(&self, count: usize) -> Vec<(Duration, u64)> {
+    if self.is_empty() || count == 0 {
+      return Vec::new();
+    }
+    let min = self.histogram.min();
+    let max = self.histogram.max();
+    let width = ((max - min) / count as u64).max(1);
+
+    let mut counts = vec![0; count];
+    for value in self.histogram.iter_recorded() {
+      let idx = (value.value_iterated_to().saturating_sub(min) / width) as usize;
+      counts[idx.min(count - 1)] += value.count_at_value();
+    }
+    counts
+      .into_iter()
+      .enumerate()
+      .map(|(idx, tasks)| {
+        let upper = if idx == count - 1 { max } else { min + width * (idx as u64 + 1) };
+        (Duration::from_micros(upper), tasks)
+      })
+      .collect()
+  }
+}
+
+/// Records `value`, growing the histogram to fit it.
+///
+/// `saturating_record` never grows it and would clamp every value past the initial range of `Histogram::new` to 2.
+fn record(histogram: &mut Histogram<u64>, value: u64) {
+  if histogram.record(value).is_err() {
+    // Beyond anything the histogram can grow to track
+    histogram.saturating_record(value);
+  }
+}
+
+/// Distribution of a per-task quantity other than a duration, e.g. bytes or a count, kept like [`DurationStats`].
+#[derive(Debug, Clone)]
+pub struct ValueStats {
+  histogram: Histogram<u64>,
+}
+
+impl Default for ValueStats {
+  fn default() -> Self {
+    ValueStats {
+      histogram: Histogram::new(3).expect("3 significant digits are supported"),
+    }
+  }
+}
+
+impl ValueStats {
+  pub fn record(&mut self, value: u64) {
+    record(&mut self.histogram, value);
+  }
+
+  pub fn count(&self) -> u64 {
+    self.histogram.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.count() == 0
+  }
+
+  pub fn min(&self) -> u64 {
+    self.histogram.min()
+  }
+
+  pub fn max(&self) -> u64 {
+    self.histogram.max()
+  }
+
+  pub fn mean(&self) -> f64 {
+    self.histogram.mean()
+  }
+
+  /// Value that `percentile` percent of the tasks stayed below, e.g. `percentile(99.9)`.
+  pub fn percentile(&self, percentile: f64) -> u64 {
+    self.histogram.value_at_quantile(percentile / 100.0)
+  }
+}
+
+/// Distributions of the resource usage of the tasks, see [`ResourceUsage`].
+#[derive(Debug, Clone, Default)]
+pub struct UsageStats {
+  /// Peak resident set size in bytes.
+  pub max_rss: ValueStats,
+  pub user_time: DurationStats,
+  pub system_time: DurationStats,
+  pub voluntary_context_switches: ValueStats,
+  pub involuntary_context_switches: ValueStats,
+}
+
+impl UsageStats {
+  pub(crate) fn record(&mut self, usage: &ResourceUsage) {
+    self.max_rss.record(usage.max_rss);
+    self.user_time.record(usage.user_time);
+    self.system_time.record(usage.system_time);
+    self.voluntary_context_switches.record(usage.voluntary_context_switches);
+    self.involuntary_context_switches.record(usage.involuntary_context_switches);
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.max_rss.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MILLI: Duration = Duration::from_millis(1);
+
+  /// Asserts that `actual` is within the 3 significant digits the histograms keep.
+  fn assert_close(actual: Duration, expected: Duration) {
+    let error = actual.abs_diff(expected).as_secs_f64();
+    assert!(error <= expected.as_secs_f64() / 1000.0, "{actual:?} is not close to {expected:?}");
+  }
+
+  fn durations(millis: &[u64]) -> DurationStats {
+    let mut stats = DurationStats::default();
+    for &millis in millis {
+      stats.record(Duration::from_millis(millis));
+    }
+    stats
+  }
+
+  #[test]
+  fn records_durations_past_the_initial_range() {
+    let stats = durations(&[500]);
+    assert_eq!(stats.count(), 1);
+    assert_close(stats.min(), 500 * MILLI);
+    assert_close(stats.max(), 500 * MILLI);
+    assert_close(stats.percentile(50.0), 500 * MILLI);
+    assert_close(stats.mean(), 500 * MILLI);
+  }
+
+  #[test]
+  fn percentiles_follow_the_distribution() {
+    let stats = durations(&(1..=100).collect::<Vec<_>>());
+    assert_close(stats.min(), MILLI);
+    assert_close(stats.max(), 100 * MILLI);
+    assert_close(stats.percentile(50.0), 50 * MILLI);
+    assert_close(stats.percentile(99.0), 99 * MILLI);
+    assert_close(stats.mean(), Duration::from_micros(50_500));
+  }
+
+  #[test]
+  fn very_long_durations_are_kept() {
+    let day = Duration::from_secs(86_400);
+    let mut stats = durations(&[1, 2]);
+    stats.record(day);
+    assert_close(stats.max(), day);
+    assert_close(stats.min(), MILLI);
+  }
+
+  #[test]
+  fn buckets_split_the_range_between_min_and_max() {
+    let stats = durations(&[10, 12, 12, 25, 40]);
+    let buckets = stats.buckets(3);
+    assert_eq!(buckets.len(), 3);
+    assert_eq!(buckets.iter().map(|&(_, tasks)| tasks).collect::<Vec<_>>(), [3, 1, 1]);
+    assert_eq!(buckets[2].0, stats.max());
+    assert!(buckets.windows(2).all(|pair| pair[0].0 < pair[1].0));
+  }
+
+  #[test]
+  fn buckets_of_a_single_value() {
+    let mut stats = DurationStats::default();
+    for _ in 0..4 {
+      stats.record(Duration::from_micros(7));
+    }
+    let buckets = stats.buckets(5);
+    assert_eq!(buckets.len(), 5);
+    assert_eq!(buckets.iter().map(|&(_, tasks)| tasks).sum::<u64>(), 4);
+    assert_eq!(buckets[0], (Duration::from_micros(8), 4));
+  }
+
+  #[test]
+  fn no_buckets_without_tasks_or_count() {
+    assert!(DurationStats::default().buckets(10).is_empty());
+    assert!(durations(&[1, 2, 3]).buckets(0).is_empty());
+  }
+
+  #[test]
+  fn value_stats_keep_large_values() {
+    let mut stats = ValueStats::default();
+    let rss = 63 * 1024 * 1024;
+    stats.record(rss);
+    stats.record(rss * 2);
+    assert_eq!(stats.count(), 2);
+    assert!(stats.min().abs_diff(rss) <= rss / 1000);
+    assert!(stats.max().abs_diff(rss * 2) <= rss / 500);
+  }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::profile::Phase;
+use crate::stats::{DurationStats, UsageStats};
+use crate::task::{TaskOutcome, TaskResult};
+
+/// Statistics of a finished pool run.
+#[derive(Debug, Clone, Default)]
+pub struct PoolSummary {
+  /// Number of tasks the run was configured for, without the skipped ones, `None` for duration based and endless runs.
+  pub total_tasks: Option<usize>,
+  pub completed: usize,
+  pub successful: usize,
+  pub failed: usize,
+  /// Failed tasks that hit the timeout.
+  pub timed_out: usize,
+  /// Successful tasks that needed more than one attempt.
+  pub succeeded_after_retry: usize,
+  /// Attempts beyond the first one, over all tasks.
+  pub retries: usize,
+  /// Open-loop launches that waited for a free slot.
+  pub delayed_launches: usize,
+  /// Open-loop launches dropped because all slots were taken.
+  pub skipped_launches: usize,
+  /// Spawning was stopped by a failure threshold or `stop_on_fail` before all tasks ran.
+  pub stopped_early: bool,
+  /// Which threshold stopped the run, set along with `stopped_early`.
+  pub stop_reason: Option<String>,
+  /// The run was interrupted through [`crate::PoolHandle`], the statistics cover the completed tasks only.
+  pub interrupted: bool,
+  pub successful_durations: DurationStats,
+  pub failed_durations: DurationStats,
+  /// Durations of the tasks by [`TaskOutcome::kind`], the count of each outcome being their count.
+  pub outcomes: BTreeMap<&'static str, DurationStats>,
+  /// Number of tasks by [`TaskOutcome::kind`] and exit code, for the tasks that exited on their own.
+  pub exit_codes: BTreeMap<(&'static str, i32), usize>,
+  /// Resource usage of the tasks whose process was reaped, failed ones included.
+  pub usage: UsageStats,
+  /// One entry per phase of the load profile, in order, empty without a profile.
+  pub phases: Vec<PhaseSummary>,
+  /// Wall-clock time of the whole run.
+  pub total_duration: Duration,
+}
+
+impl PoolSummary {
+  pub(crate) fn record(&mut self, result: &TaskResult) {
+    self.completed += 1;
+    self.retries += result.attempt - 1;
+    if result.outcome.is_success() {
+      self.successful += 1;
+      if result.attempt > 1 {
+        self.succeeded_after_retry += 1;
+      }
+      self.successful_durations.record(result.duration);
+    } else {
+      self.failed += 1;
+      if matches!(result.outcome, TaskOutcome::TimedOut(_)) {
+        self.timed_out += 1;
+      }
+      self.failed_durations.record(result.duration);
+    }
+    self.outcomes.entry(result.outcome.kind()).or_default().record(result.duration);
+    if let Some(exit_code) = result.exit_code() {
+      *self.exit_codes.entry((result.outcome.kind(), exit_code)).or_default() += 1;
+    }
+    if let Some(usage) = &result.usage {
+      self.usage.record(usage);
+    }
+  }
+
+  /// Percentage of the tasks that succeeded, out of `total_tasks` if set, out of the completed tasks otherwise.
+  pub fn success_rate(&self) -> f64 {
+    let total_tasks = self.total_tasks.unwrap_or(self.completed);
+    if total_tasks > 0 {
+      (self.successful as f64 / total_tasks as f64) * 100.0
+    } else {
+      0.0
+    }
+  }
+}
+
+/// Statistics of the tasks started during one phase of a load profile.
+#[derive(Debug, Clone)]
+pub struct PhaseSummary {
+  pub phase: Phase,
+  pub completed: usize,
+  pub successful: usize,
+  pub failed: usize,
+  /// Durations of all tasks of the phase, successful or not.
+  pub durations: DurationStats,
+}
+
+impl PhaseSummary {
+  pub(crate) fn new(phase: Phase) -> Self {
+    PhaseSummary {
+      phase,
+      completed: 0,
+      successful: 0,
+      failed: 0,
+      durations: DurationStats::default(),
+    }
+  }
+
+  pub(crate) fn record(&mut self, result: &TaskResult) {
+    self.completed += 1;
+    if result.outcome.is_success() {
+      self.successful += 1;
+    } else {
+      self.failed += 1;
+    }
+    self.durations.record(result.duration);
+  }
+
+  /// Percentage of the completed tasks of the phase that failed.
+  pub fn failure_rate(&self) -> f64 {
+    if self.completed > 0 {
+      (self.failed as f64 / self.completed as f64) * 100.0
+    } else {
+      0.0
+    }
+  }
+}
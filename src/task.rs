@@ -0,0 +1,305 @@
+use std::fmt;
+use std::os::unix::process::ExitStatusExt;
+use std::pin::pin;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::SystemTime;
+use tokio::time::{Duration, Instant};
+
+use crate::config::PoolConfig;
+use crate::pool::{EventSender, PoolEvent};
+use crate::process::{self, Completion, ProcessGroups, ResourceUsage, TaskProcess};
+use crate::template::{self, TaskContext};
+
+/// One invocation of the command template.
+#[derive(Debug, Clone)]
+pub struct TaskSpec {
+  /// Task id, starting at 1.
+  pub id: usize,
+  /// Slot the task occupies, between 1 and the concurrency.
+  pub slot: usize,
+  /// Input line the task was built from.
+  pub input: Option<String>,
+  pub program: String,
+  pub args: Vec<String>,
+}
+
+impl TaskSpec {
+  /// Expands the placeholders of `command` for one task.
+  pub fn from_template(command: &[String], input: Option<&str>, id: usize, slot: usize) -> Self {
+    let ctx = TaskContext { input, task_id: id, slot };
+    let (program, args) = match command.split_first() {
+      Some((program, args)) => template::expand_command(program, args, &ctx),
+      None => (String::new(), Vec::new()),
+    };
+    TaskSpec {
+      id,
+      slot,
+      input: input.map(str::to_string),
+      program,
+      args,
+    }
+  }
+
+  /// The expanded command line, for display.
+  pub fn command_line(&self) -> String {
+    let mut line = self.program.clone();
+    for arg in &self.args {
+      line.push(' ');
+      line.push_str(arg);
+    }
+    line
+  }
+}
+
+/// How a task ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskOutcome {
+  /// The command exited with an accepted status and met the success criteria.
+  Success { exit_code: i32 },
+  /// The command exited with a status not accepted as success, any non-zero one by default, but possibly 0 too.
+  UnacceptedExit { exit_code: i32 },
+  /// The command exited with an accepted status but did not meet the other success criteria.
+  Rejected { exit_code: i32, reason: String },
+  /// The command was killed by a signal, e.g. it crashed or got the signal forwarded on interrupt.
+  Signaled { signal: i32, core_dumped: bool },
+  /// The command ran longer than the timeout and its process group was stopped.
+  TimedOut(Termination),
+  /// The command could not be spawned, e.g. the program does not exist.
+  SpawnFailed(String),
+  /// The task was cancelled through [`crate::PoolHandle::cancel`] and its process group killed.
+  Cancelled,
+  /// Waiting for the command failed.
+  Error(String),
+}
+
+impl TaskOutcome {
+  pub fn is_success(&self) -> bool {
+    matches!(self, TaskOutcome::Success { .. })
+  }
+
+  /// Short name of the variant, e.g. `unaccepted_exit`, to group results by outcome.
+  pub fn kind(&self) -> &'static str {
+    match self {
+      TaskOutcome::Success { .. } => "success",
+      TaskOutcome::UnacceptedExit { .. } => "unaccepted_exit",
+      TaskOutcome::Rejected { .. } => "rejected",
+      TaskOutcome::Signaled { .. } => "signaled",
+      TaskOutcome::TimedOut(_) => "timed_out",
+      TaskOutcome::SpawnFailed(_) => "spawn_failed",
+      TaskOutcome::Cancelled => "cancelled",
+      TaskOutcome::Error(_) => "error",
+    }
+  }
+}
+
+impl fmt::Display for TaskOutcome {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TaskOutcome::Success { exit_code } => write!(f, "Success (Exit Code: {exit_code})"),
+      TaskOutcome::UnacceptedExit { exit_code } => write!(f, "Failed (Exit Code: {exit_code})"),
+      TaskOutcome::Rejected { exit_code, reason } => write!(f, "Failed: {reason} (Exit Code: {exit_code})"),
+      TaskOutcome::Signaled { signal, core_dumped } => {
+        match signal_name(*signal) {
+          Some(name) => write!(f, "Killed by {name} (Signal: {signal})")?,
+          None => write!(f, "Killed by signal {signal}")?,
+        }
+        if *core_dumped {
+          write!(f, ", core dumped")?;
+        }
+        Ok(())
+      }
+      TaskOutcome::TimedOut(Termination::Terminated) => write!(f, "Timed Out (SIGTERM)"),
+      TaskOutcome::TimedOut(Termination::Killed) => write!(f, "Timed Out (SIGTERM, then SIGKILL after the grace period)"),
+      TaskOutcome::SpawnFailed(e) => write!(f, "Spawn Failed: {e}"),
+      TaskOutcome::Cancelled => write!(f, "Cancelled"),
+      TaskOutcome::Error(e) => write!(f, "Error: {e}"),
+    }
+  }
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+  let name = match signal {
+    libc::SIGHUP => "SIGHUP",
+    libc::SIGINT => "SIGINT",
+    libc::SIGQUIT => "SIGQUIT",
+    libc::SIGILL => "SIGILL",
+    libc::SIGTRAP => "SIGTRAP",
+    libc::SIGABRT => "SIGABRT",
+    libc::SIGBUS => "SIGBUS",
+    libc::SIGFPE => "SIGFPE",
+    libc::SIGKILL => "SIGKILL",
+    libc::SIGUSR1 => "SIGUSR1",
+    libc::SIGSEGV => "SIGSEGV",
+    libc::SIGUSR2 => "SIGUSR2",
+    libc::SIGPIPE => "SIGPIPE",
+    libc::SIGALRM => "SIGALRM",
+    libc::SIGTERM => "SIGTERM",
+    libc::SIGXCPU => "SIGXCPU",
+    libc::SIGXFSZ => "SIGXFSZ",
+    _ => return None,
+  };
+  Some(name)
+}
+
+/// How the process group of a timed out task was stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+  /// The group exited within the grace period after SIGTERM.
+  Terminated,
+  /// Processes of the group were still alive after the grace period and got SIGKILL.
+  Killed,
+}
+
+/// Output stream of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+  Stdout,
+  Stderr,
+}
+
+/// A finished task with its captured output.
+#[derive(Debug, Clone)]
+pub struct TaskResult {
+  pub task: TaskSpec,
+  pub outcome: TaskOutcome,
+  /// Attempt that produced this result, starting at 1.
+  pub attempt: usize,
+  /// Exit status of the process, if it was spawned and exited on its own.
+  pub status: Option<ExitStatus>,
+  /// Resource usage of the process, if it was spawned and reaped, timed out ones included.
+  pub usage: Option<ResourceUsage>,
+  /// Wall-clock time this attempt started at.
+  pub started_at: SystemTime,
+  /// Duration of this attempt.
+  pub duration: Duration,
+  pub stdout: String,
+  pub stderr: String,
+}
+
+impl TaskResult {
+  pub fn exit_code(&self) -> Option<i32> {
+    self.status.and_then(|status| status.code())
+  }
+
+  /// Signal that terminated the process.
+  pub fn signal(&self) -> Option<i32> {
+    self.status.and_then(|status| status.signal())
+  }
+
+  pub fn finished_at(&self) -> SystemTime {
+    self.started_at + self.duration
+  }
+}
+
+/// Exposes the identity of a task to the child process through environment variables.
+fn set_task_env(cmd: &mut Command, config: &PoolConfig, task: &TaskSpec, attempt: usize) {
+  if let Some(env) = &config.env {
+    cmd.env_clear().envs(env.iter().map(|(key, value)| (key, value)));
+  }
+  cmd
+    .env("COMMAND_POOL_RUN_ID", &config.run_id)
+    .env("COMMAND_POOL_TASK_ID", task.id.to_string())
+    .env("COMMAND_POOL_SLOT", task.slot.to_string())
+    .env("COMMAND_POOL_ATTEMPT", attempt.to_string());
+  if let Some(total_tasks) = config.total_tasks {
+    cmd.env("COMMAND_POOL_TOTAL", total_tasks.to_string());
+  }
+}
+
+/// Runs a task to completion, capturing its output and streaming it line by line if `stream_output` is set.
+pub(crate) async fn execute(
+  task: TaskSpec,
+  attempt: usize,
+  config: &PoolConfig,
+  groups: &ProcessGroups,
+  events: &EventSender,
+) -> TaskResult {
+  let mut cmd = Command::new(&task.program);
+  cmd
+    .args(&task.args)
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+  set_task_env(&mut cmd, config, &task, attempt);
+
+  let started_at = SystemTime::now();
+  let task_start_time = Instant::now();
+  let (outcome, status, usage, stdout, stderr) = match TaskProcess::spawn(&mut cmd) {
+    Ok((mut process, stdout, stderr)) => {
+      let stream_line = |stream: OutputStream, line: &[u8]| {
+        if config.stream_output {
+          events.send(PoolEvent::TaskOutput {
+            task_id: task.id,
+            stream,
+            line: String::from_utf8_lossy(line).trim_end_matches(['\n', '\r']).to_string(),
+          });
+        }
+      };
+      let (mut stdout_buf, mut stderr_buf) = (Vec::new(), Vec::new());
+      groups.register(task.id, process.pgid());
+      let (outcome, status, usage) = {
+        let stdout = process::read_pipe(stdout, &mut stdout_buf, |line| stream_line(OutputStream::Stdout, line));
+        let stderr = process::read_pipe(stderr, &mut stderr_buf, |line| stream_line(OutputStream::Stderr, line));
+        let pipes = pin!(async { tokio::join!(stdout, stderr) });
+        wait_child(Completion::new(&mut process, pipes), config).await
+      };
+      groups.unregister(task.id);
+      (
+        outcome,
+        status,
+        usage,
+        String::from_utf8_lossy(&stdout_buf).to_string(),
+        String::from_utf8_lossy(&stderr_buf).to_string(),
+      )
+    }
+    Err(e) => (TaskOutcome::SpawnFailed(e.to_string()), None, None, String::new(), String::new()),
+  };
+  let duration = task_start_time.elapsed();
+  let outcome = match outcome {
+    TaskOutcome::Success { exit_code } => match config.success.check(&stdout, &stderr, duration) {
+      Some(reason) => TaskOutcome::Rejected { exit_code, reason },
+      None => TaskOutcome::Success { exit_code },
+    },
+    outcome => outcome,
+  };
+
+  TaskResult {
+    task,
+    outcome,
+    attempt,
+    status,
+    usage,
+    started_at,
+    duration,
+    stdout,
+    stderr,
+  }
+}
+
+/// Waits for the task process to exit and its output to be read,
+/// stopping its process group when that takes longer than the timeout.
+async fn wait_child<P: Future>(
+  mut completion: Completion<'_, P>,
+  config: &PoolConfig,
+) -> (TaskOutcome, Option<ExitStatus>, Option<ResourceUsage>) {
+  let deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+  if !completion.wait_until(deadline).await {
+    let termination = completion.terminate(config.kill_grace).await;
+    let usage = completion.exited.and_then(Result::ok).map(|(_, usage)| usage);
+    return (TaskOutcome::TimedOut(termination), None, usage);
+  }
+  match completion.exited.expect("the leader exited") {
+    Ok((status, usage)) => {
+      let outcome = match status.code() {
+        Some(exit_code) if config.success.accepts_exit_code(exit_code) => TaskOutcome::Success { exit_code },
+        Some(exit_code) => TaskOutcome::UnacceptedExit { exit_code },
+        None => TaskOutcome::Signaled {
+          signal: status.signal().unwrap_or_default(),
+          core_dumped: status.core_dumped(),
+        },
+      };
+      (outcome, Some(status), Some(usage))
+    }
+    Err(e) => (TaskOutcome::Error(e.to_string()), None, None),
+  }
+}
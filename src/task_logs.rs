@@ -0,0 +1,16 @@
+//! Per-task log files for `--output-dir`.
+
+use command_pool::TaskResult;
+use std::path::Path;
+
+use crate::json;
+
+/// Writes the output of a task and its metadata to `dir/<task id>/`.
+pub async fn write(dir: &Path, result: &TaskResult) -> std::io::Result<()> {
+  let task_dir = dir.join(result.task.id.to_string());
+  tokio::fs::create_dir_all(&task_dir).await?;
+  tokio::fs::write(task_dir.join("stdout"), &result.stdout).await?;
+  tokio::fs::write(task_dir.join("stderr"), &result.stderr).await?;
+  let meta = serde_json::to_string_pretty(&json::task_result(result))?;
+  tokio::fs::write(task_dir.join("meta.json"), meta).await
+}
@@ -0,0 +1,207 @@
+//! Placeholder expansion for the command template.
+//!
+//! Supported tokens, mostly following GNU parallel:
+//!
+//! - `{}` the task input line
+//! - `{.}` the input without its extension
+//! - `{/}` the basename of the input
+//! - `{//}` the directory of the input
+//! - `{/.}` the basename of the input without its extension
+//! - `{#}` the task id, starting at 1
+//! - `{%}` the slot number, between 1 and the concurrency
+//!
+//! Any other `{...}` sequence is left untouched, so shell snippets like `awk '{print $1}'` keep working.
+//! Without an input, the input tokens are left untouched too, so `find . -exec wc -l {} +` keeps working.
+
+/// Values available to placeholders when expanding the command of one task.
+pub struct TaskContext<'a> {
+  pub input: Option<&'a str>,
+  pub task_id: usize,
+  pub slot: usize,
+}
+
+const INPUT_PLACEHOLDERS: [&str; 5] = ["{}", "{.}", "{/}", "{//}", "{/.}"];
+
+/// Expands the program and its arguments for one task.
+///
+/// When the template does not reference the input through a placeholder, the input is appended as the last argument.
+pub fn expand_command(program: &str, args: &[String], ctx: &TaskContext) -> (String, Vec<String>) {
+  let mut expanded_args: Vec<String> = args.iter().map(|arg| expand(arg, ctx)).collect();
+  if let Some(input) = ctx.input
+    && !uses_input(program)
+    && !args.iter().any(|arg| uses_input(arg))
+  {
+    expanded_args.push(input.to_string());
+  }
+  (expand(program, ctx), expanded_args)
+}
+
+fn uses_input(part: &str) -> bool {
+  INPUT_PLACEHOLDERS.iter().any(|token| part.contains(token))
+}
+
+/// Replaces every known placeholder in `template`.
+pub fn expand(template: &str, ctx: &TaskContext) -> String {
+  let mut result = String::with_capacity(template.len());
+  let mut rest = template;
+
+  while let Some(open) = rest.find('{') {
+    result.push_str(&rest[..open]);
+    let candidate = &rest[open..];
+    let token_end = candidate.find('}').map(|close| close + 1);
+    let replacement = token_end.and_then(|end| match &candidate[..end] {
+      "{}" => ctx.input.map(str::to_string),
+      "{.}" => ctx.input.map(|input| without_extension(input).to_string()),
+      "{/}" => ctx.input.map(|input| basename(input).to_string()),
+      "{//}" => ctx.input.map(|input| dirname(input).to_string()),
+      "{/.}" => ctx.input.map(|input| without_extension(basename(input)).to_string()),
+      "{#}" => Some(ctx.task_id.to_string()),
+      "{%}" => Some(ctx.slot.to_string()),
+      _ => None,
+    });
+    match (replacement, token_end) {
+      (Some(value), Some(end)) => {
+        result.push_str(&value);
+        rest = &candidate[end..];
+      }
+      _ => {
+        result.push('{');
+        rest = &candidate[1..];
+      }
+    }
+  }
+  result.push_str(rest);
+  result
+}
+
+/// Strips the trailing slashes of a directory path, keeping the root.
+fn trim_trailing_slashes(path: &str) -> &str {
+  match path.trim_end_matches('/') {
+    "" if path.starts_with('/') => "/",
+    trimmed => trimmed,
+  }
+}
+
+fn basename(path: &str) -> &str {
+  let path = trim_trailing_slashes(path);
+  if path == "/" {
+    return path;
+  }
+  path.rsplit('/').next().unwrap_or(path)
+}
+
+fn dirname(path: &str) -> &str {
+  let path = trim_trailing_slashes(path);
+  match path.rfind('/') {
+    Some(0) => "/",
+    Some(idx) => &path[..idx],
+    None => ".",
+  }
+}
+
+fn without_extension(path: &str) -> &str {
+  let base_start = path.rfind('/').map_or(0, |idx| idx + 1);
+  match path[base_start..].rfind('.') {
+    Some(dot) if dot > 0 => &path[..base_start + dot],
+    _ => path,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ctx(input: Option<&str>) -> TaskContext<'_> {
+    TaskContext {
+      input,
+      task_id: 7,
+      slot: 2,
+    }
+  }
+
+  fn expand_with(template: &str, input: &str) -> String {
+    expand(template, &ctx(Some(input)))
+  }
+
+  #[test]
+  fn input_tokens() {
+    assert_eq!(expand_with("{}", "dir/sub/file.tar.gz"), "dir/sub/file.tar.gz");
+    assert_eq!(expand_with("{.}", "dir/sub/file.tar.gz"), "dir/sub/file.tar");
+    assert_eq!(expand_with("{/}", "dir/sub/file.tar.gz"), "file.tar.gz");
+    assert_eq!(expand_with("{//}", "dir/sub/file.tar.gz"), "dir/sub");
+    assert_eq!(expand_with("{/.}", "dir/sub/file.tar.gz"), "file.tar");
+  }
+
+  #[test]
+  fn task_tokens() {
+    assert_eq!(expand("task-{#}-slot-{%}", &ctx(None)), "task-7-slot-2");
+    assert_eq!(expand_with("{#}:{}", "a"), "7:a");
+  }
+
+  #[test]
+  fn inputs_without_directory_or_extension() {
+    assert_eq!(expand_with("{//}", "file.txt"), ".");
+    assert_eq!(expand_with("{//}", "/file.txt"), "/");
+    assert_eq!(expand_with("{.}", "file"), "file");
+    assert_eq!(expand_with("{.}", "dir.d/file"), "dir.d/file");
+  }
+
+  #[test]
+  fn dotfiles_keep_their_name() {
+    assert_eq!(expand_with("{.}", ".bashrc"), ".bashrc");
+    assert_eq!(expand_with("{/.}", "home/.bashrc"), ".bashrc");
+    assert_eq!(expand_with("{/.}", "home/.config.bak"), ".config");
+  }
+
+  #[test]
+  fn trailing_slashes_are_ignored() {
+    assert_eq!(expand_with("{/}", "dir/sub/"), "sub");
+    assert_eq!(expand_with("{//}", "dir/sub/"), "dir");
+    assert_eq!(expand_with("{/}", "/"), "/");
+    assert_eq!(expand_with("{//}", "/"), "/");
+  }
+
+  #[test]
+  fn other_braces_are_left_untouched() {
+    assert_eq!(expand_with("awk '{print $1}'", "x"), "awk '{print $1}'");
+    assert_eq!(expand_with("{{}}", "x"), "{x}");
+    assert_eq!(expand_with("{a{}", "x"), "{ax");
+    assert_eq!(expand_with("unclosed {", "x"), "unclosed {");
+    assert_eq!(expand_with("{/x}", "x"), "{/x}");
+  }
+
+  #[test]
+  fn input_tokens_without_input_are_left_untouched() {
+    let ctx = ctx(None);
+    assert_eq!(expand("{} {.} {/} {//} {/.}", &ctx), "{} {.} {/} {//} {/.}");
+    assert_eq!(expand("{\"id\": {#}}", &ctx), "{\"id\": 7}");
+  }
+
+  #[test]
+  fn input_is_appended_unless_a_placeholder_uses_it() {
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+      expand_command("gzip", &args(&["-k"]), &ctx(Some("a.txt"))),
+      ("gzip".to_string(), args(&["-k", "a.txt"]))
+    );
+    assert_eq!(
+      expand_command("convert", &args(&["{}", "{.}.png"]), &ctx(Some("a.jpg"))),
+      ("convert".to_string(), args(&["a.jpg", "a.png"]))
+    );
+    assert_eq!(
+      expand_command("./{/}", &args(&["--id", "{#}"]), &ctx(Some("bin/run"))),
+      ("./run".to_string(), args(&["--id", "7"]))
+    );
+    // {#} alone does not consume the input
+    assert_eq!(
+      expand_command("echo", &args(&["{#}"]), &ctx(Some("a"))),
+      ("echo".to_string(), args(&["7", "a"]))
+    );
+  }
+
+  #[test]
+  fn nothing_is_appended_without_input() {
+    let args = vec!["-exec".to_string(), "wc".to_string(), "{}".to_string(), "+".to_string()];
+    assert_eq!(expand_command("find", &args, &ctx(None)), ("find".to_string(), args.clone()));
+  }
+}
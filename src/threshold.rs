@@ -0,0 +1,209 @@
+//! Failure thresholds that stop a run early, a generalisation of `stop_on_fail`.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+/// Failure counts past which no new task is launched.
+#[derive(Debug, Clone)]
+pub struct FailureThresholds {
+  /// Stop once this many tasks failed.
+  pub max_failures: Option<usize>,
+  /// Stop when more than this percentage of the last `window` completed tasks failed.
+  pub max_failure_rate: Option<f64>,
+  /// Number of completed tasks the failure rate is computed over, it is only checked once that many completed.
+  ///
+  /// A window of 0 disables `max_failure_rate`.
+  pub window: usize,
+  /// Stop once this many tasks failed in a row.
+  pub max_consecutive: Option<usize>,
+}
+
+impl Default for FailureThresholds {
+  fn default() -> Self {
+    FailureThresholds {
+      max_failures: None,
+      max_failure_rate: None,
+      window: 100,
+      max_consecutive: None,
+    }
+  }
+}
+
+/// What happens to the running tasks when a failure threshold stops the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StopMode {
+  /// Kill the running tasks right away, their results are not recorded.
+  #[default]
+  Abort,
+  /// Launch no new task and let the running ones finish.
+  Drain,
+}
+
+impl FromStr for StopMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "abort" => Ok(StopMode::Abort),
+      "drain" => Ok(StopMode::Drain),
+      other => Err(format!("unknown stop mode `{other}`, expected abort or drain")),
+    }
+  }
+}
+
+impl fmt::Display for StopMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      StopMode::Abort => write!(f, "abort"),
+      StopMode::Drain => write!(f, "drain"),
+    }
+  }
+}
+
+/// Failure counts of a run, checked against the thresholds after every task.
+#[derive(Default)]
+pub(crate) struct FailureTracker {
+  failures: usize,
+  consecutive: usize,
+  /// Whether each of the last `window` tasks failed, oldest first.
+  window: VecDeque<bool>,
+  window_failures: usize,
+}
+
+impl FailureTracker {
+  /// Records a finished task, returns why the run must stop if a threshold is crossed.
+  pub(crate) fn record(&mut self, thresholds: &FailureThresholds, failed: bool) -> Option<String> {
+    if failed {
+      self.failures += 1;
+      self.consecutive += 1;
+      self.window_failures += 1;
+    } else {
+      self.consecutive = 0;
+    }
+    self.window.push_back(failed);
+    if self.window.len() > thresholds.window && self.window.pop_front() == Some(true) {
+      self.window_failures -= 1;
+    }
+
+    if let Some(max_failures) = thresholds.max_failures
+      && self.failures >= max_failures
+    {
+      return Some(format!("{} tasks failed", self.failures));
+    }
+    if let Some(max_consecutive) = thresholds.max_consecutive
+      && self.consecutive >= max_consecutive
+    {
+      return Some(format!("{} tasks failed in a row", self.consecutive));
+    }
+    if let Some(max_failure_rate) = thresholds.max_failure_rate
+      && thresholds.window > 0
+      && self.window.len() == thresholds.window
+    {
+      let failure_rate = self.window_failures as f64 / thresholds.window as f64 * 100.0;
+      if failure_rate > max_failure_rate {
+        return Some(format!(
+          "{failure_rate:.1}% of the last {} tasks failed, above {max_failure_rate}%",
+          thresholds.window
+        ));
+      }
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Records `failures` in order, returns the index of the task that stopped the run with the reason.
+  fn first_stop(thresholds: &FailureThresholds, failures: &[bool]) -> Option<(usize, String)> {
+    let mut tracker = FailureTracker::default();
+    failures
+      .iter()
+      .enumerate()
+      .find_map(|(idx, &failed)| tracker.record(thresholds, failed).map(|reason| (idx, reason)))
+  }
+
+  #[test]
+  fn no_threshold_never_stops() {
+    assert_eq!(first_stop(&FailureThresholds::default(), &[true; 500]), None);
+  }
+
+  #[test]
+  fn max_failures_counts_every_failure() {
+    let thresholds = FailureThresholds {
+      max_failures: Some(3),
+      ..FailureThresholds::default()
+    };
+    let stop = first_stop(&thresholds, &[true, false, true, false, false, true, true]);
+    assert_eq!(stop, Some((5, "3 tasks failed".to_string())));
+  }
+
+  #[test]
+  fn a_success_resets_the_consecutive_failures() {
+    let thresholds = FailureThresholds {
+      max_consecutive: Some(3),
+      ..FailureThresholds::default()
+    };
+    assert_eq!(first_stop(&thresholds, &[true, true, false, true, true, false, true]), None);
+    let stop = first_stop(&thresholds, &[true, true, false, true, true, true]);
+    assert_eq!(stop, Some((5, "3 tasks failed in a row".to_string())));
+  }
+
+  #[test]
+  fn failure_rate_is_only_checked_once_the_window_is_full() {
+    let thresholds = FailureThresholds {
+      max_failure_rate: Some(50.0),
+      window: 4,
+      ..FailureThresholds::default()
+    };
+    // 3 failures out of 3 completed tasks, but the window is not full yet
+    assert_eq!(first_stop(&thresholds, &[true, true, true]), None);
+    let stop = first_stop(&thresholds, &[true, true, true, false]);
+    assert_eq!(stop.map(|(idx, _)| idx), Some(3));
+  }
+
+  #[test]
+  fn failure_rate_must_exceed_the_threshold() {
+    let thresholds = FailureThresholds {
+      max_failure_rate: Some(50.0),
+      window: 4,
+      ..FailureThresholds::default()
+    };
+    assert_eq!(first_stop(&thresholds, &[true, false, true, false, true, false]), None);
+  }
+
+  #[test]
+  fn old_results_leave_the_window() {
+    let thresholds = FailureThresholds {
+      max_failure_rate: Some(50.0),
+      window: 4,
+      ..FailureThresholds::default()
+    };
+    // The early failures are evicted by the successes before the window reaches 3 failures out of 4
+    let mut failures = vec![true, true, false, false, false, false, false, false];
+    assert_eq!(first_stop(&thresholds, &failures), None);
+    failures.extend([true, true, true]);
+    let stop = first_stop(&thresholds, &failures);
+    assert_eq!(stop, Some((10, "75.0% of the last 4 tasks failed, above 50%".to_string())));
+  }
+
+  #[test]
+  fn an_empty_window_disables_the_failure_rate() {
+    let thresholds = FailureThresholds {
+      max_failure_rate: Some(0.0),
+      window: 0,
+      ..FailureThresholds::default()
+    };
+    assert_eq!(first_stop(&thresholds, &[true; 10]), None);
+  }
+
+  #[test]
+  fn stop_mode_round_trips() {
+    for mode in [StopMode::Abort, StopMode::Drain] {
+      assert_eq!(mode.to_string().parse::<StopMode>(), Ok(mode));
+    }
+    assert!("later".parse::<StopMode>().is_err());
+  }
+}
@@ -0,0 +1,360 @@
+//! Full-screen dashboard for `--tui`: running tasks, recent failures, a latency sparkline and keys to steer the run.
+
+use command_pool::{OutputStream, PoolEvent, PoolHandle, TaskResult, TaskSpec};
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Row, Sparkline, Table, TableState};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::time;
+
+use crate::{TaskRecorder, format_duration_custom};
+
+/// How often the screen is redrawn.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the key reader waits for a key before checking whether the dashboard is gone.
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Number of failed tasks listed, the most recent first.
+const MAX_FAILURES: usize = 20;
+/// Number of stderr lines shown under each failure.
+const STDERR_TAIL: usize = 3;
+/// Number of output lines kept per running task for the dump key.
+const OUTPUT_LINES: usize = 1000;
+/// Number of task durations kept for the sparkline.
+const SPARKLINE_POINTS: usize = 500;
+
+struct RunningTask {
+  spec: TaskSpec,
+  started: Instant,
+  output: VecDeque<(OutputStream, String)>,
+}
+
+struct Dashboard {
+  total_tasks: Option<usize>,
+  start_time: Instant,
+  running: BTreeMap<usize, RunningTask>,
+  failures: VecDeque<TaskResult>,
+  /// Durations of the last tasks in milliseconds, oldest first.
+  durations: VecDeque<u64>,
+  completed: usize,
+  succeeded: usize,
+  failed: usize,
+  /// Index of the selected task in `running`.
+  selected: usize,
+  /// Outcome of the last key action.
+  status: String,
+  stopping: bool,
+  /// The event stream ended, the run is over.
+  finished: bool,
+}
+
+impl Dashboard {
+  fn new(total_tasks: Option<usize>) -> Self {
+    Dashboard {
+      total_tasks,
+      start_time: Instant::now(),
+      running: BTreeMap::new(),
+      failures: VecDeque::new(),
+      durations: VecDeque::new(),
+      completed: 0,
+      succeeded: 0,
+      failed: 0,
+      selected: 0,
+      status: String::new(),
+      stopping: false,
+      finished: false,
+    }
+  }
+
+  fn record(&mut self, event: PoolEvent) {
+    match event {
+      PoolEvent::TaskStarted { task, .. } => {
+        let task = RunningTask {
+          spec: task,
+          started: Instant::now(),
+          output: VecDeque::new(),
+        };
+        self.running.insert(task.spec.id, task);
+      }
+      PoolEvent::TaskOutput { task_id, stream, line } => {
+        if let Some(task) = self.running.get_mut(&task_id) {
+          if task.output.len() == OUTPUT_LINES {
+            task.output.pop_front();
+          }
+          task.output.push_back((stream, line));
+        }
+      }
+      PoolEvent::TaskRetrying { .. } => {}
+      PoolEvent::TaskFinished { result, .. } => {
+        self.running.remove(&result.task.id);
+        self.completed += 1;
+        if self.durations.len() == SPARKLINE_POINTS {
+          self.durations.pop_front();
+        }
+        self.durations.push_back(result.duration.as_millis() as u64);
+        if result.outcome.is_success() {
+          self.succeeded += 1;
+        } else {
+          self.failed += 1;
+          if self.failures.len() == MAX_FAILURES {
+            self.failures.pop_back();
+          }
+          self.failures.push_front(result);
+        }
+      }
+    }
+  }
+
+  fn selected_task(&self) -> Option<&RunningTask> {
+    self.running.values().nth(self.selected.min(self.running.len().saturating_sub(1)))
+  }
+
+  /// Acts on a key press, returns false when the dashboard must close.
+  fn handle_key(&mut self, key: KeyEvent, handle: &PoolHandle) -> bool {
+    if key.kind != KeyEventKind::Press {
+      return true;
+    }
+    match key.code {
+      KeyCode::Char('q') | KeyCode::Esc => return self.stop(handle),
+      KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return self.stop(handle),
+      KeyCode::Char('p') => {
+        if handle.is_paused() {
+          handle.resume();
+          self.status = "Resumed".to_string();
+        } else {
+          handle.pause();
+          self.status = "Paused, no new task is launched".to_string();
+        }
+      }
+      KeyCode::Char('+') | KeyCode::Char('=') => {
+        handle.set_concurrency(handle.concurrency() + 1);
+        self.status = format!("Concurrency raised to {}", handle.concurrency());
+      }
+      KeyCode::Char('-') => {
+        handle.set_concurrency(handle.concurrency().saturating_sub(1).max(1));
+        self.status = format!("Concurrency lowered to {}", handle.concurrency());
+      }
+      KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+      KeyCode::Down => self.selected = (self.selected + 1).min(self.running.len().saturating_sub(1)),
+      KeyCode::Char('k') => {
+        if let Some(task) = self.selected_task() {
+          let id = task.spec.id;
+          self.status = if handle.cancel(id) {
+            format!("Killed task {id}")
+          } else {
+            format!("Task {id} is no longer running")
+          };
+        }
+      }
+      KeyCode::Char('d') => {
+        if let Some(task) = self.selected_task() {
+          let id = task.spec.id;
+          let path = PathBuf::from(format!("command-pool-task-{id}.log"));
+          let status = match dump_output(task, &path) {
+            Ok(()) => format!("Output of task {id} written to {}", path.display()),
+            Err(e) => format!("Could not write {}: {e}", path.display()),
+          };
+          self.status = status;
+        }
+      }
+      _ => {}
+    }
+    true
+  }
+
+  /// Quits once the run is over, otherwise stops the run: gracefully first, then by killing the running tasks.
+  fn stop(&mut self, handle: &PoolHandle) -> bool {
+    if self.finished {
+      return false;
+    }
+    if self.stopping {
+      handle.kill();
+      self.status = "Killing running tasks...".to_string();
+    } else {
+      self.stopping = true;
+      handle.interrupt(libc::SIGINT);
+      self.status = "Stopping, waiting for running tasks to finish (q again to kill them)...".to_string();
+    }
+    true
+  }
+
+  fn render(&self, frame: &mut Frame, handle: &PoolHandle) {
+    let [header, sparkline, running, failures, footer] = Layout::vertical([
+      Constraint::Length(4),
+      Constraint::Length(5),
+      Constraint::Min(5),
+      Constraint::Length(12),
+      Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let elapsed = self.start_time.elapsed();
+    let throughput = self.completed as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    let completed = match self.total_tasks {
+      Some(total_tasks) => format!("{}/{total_tasks}", self.completed),
+      None => self.completed.to_string(),
+    };
+    let state = if self.finished {
+      "finished, q to quit"
+    } else if self.stopping {
+      "stopping"
+    } else if handle.is_paused() {
+      "paused"
+    } else {
+      "running"
+    };
+    let counters = vec![
+      Line::from(format!(
+        "Completed: {completed} | Running: {} | Succeeded: {} | Failed: {} | {throughput:.1} tasks/s | Elapsed: {}",
+        self.running.len(),
+        self.succeeded,
+        self.failed,
+        format_duration_custom(elapsed)
+      )),
+      Line::from(format!("Concurrency: {} | State: {state}", handle.concurrency())),
+    ];
+    frame.render_widget(Paragraph::new(counters).block(Block::bordered().title(" command-pool ")), header);
+
+    let points = sparkline.width.saturating_sub(2) as usize;
+    let durations: Vec<u64> = self
+      .durations
+      .iter()
+      .skip(self.durations.len().saturating_sub(points))
+      .copied()
+      .collect();
+    frame.render_widget(
+      Sparkline::default()
+        .block(Block::bordered().title(" Task duration (ms) "))
+        .data(&durations),
+      sparkline,
+    );
+
+    let rows = self.running.values().map(|task| {
+      Row::new(vec![
+        task.spec.id.to_string(),
+        task.spec.slot.to_string(),
+        format_duration_custom(task.started.elapsed()),
+        task.spec.command_line(),
+      ])
+    });
+    let table = Table::new(
+      rows,
+      [
+        Constraint::Length(8),
+        Constraint::Length(6),
+        Constraint::Length(10),
+        Constraint::Fill(1),
+      ],
+    )
+    .header(Row::new(vec!["Task", "Slot", "Elapsed", "Command"]).bold())
+    .row_highlight_style(Style::new().reversed())
+    .block(Block::bordered().title(format!(" Running tasks ({}) ", self.running.len())));
+    let mut table_state =
+      TableState::default().with_selected((!self.running.is_empty()).then(|| self.selected.min(self.running.len() - 1)));
+    frame.render_stateful_widget(table, running, &mut table_state);
+
+    let items = self.failures.iter().map(|result| {
+      let mut lines = vec![
+        Line::from(format!(
+          "[Task {}] {} after {}",
+          result.task.id,
+          result.outcome,
+          format_duration_custom(result.duration)
+        ))
+        .red(),
+      ];
+      let stderr: Vec<&str> = result.stderr.lines().collect();
+      for line in &stderr[stderr.len().saturating_sub(STDERR_TAIL)..] {
+        lines.push(Line::from(format!("    {line}")).dim());
+      }
+      ListItem::new(lines)
+    });
+    frame.render_widget(
+      List::new(items).block(Block::bordered().title(format!(" Recent failures ({}) ", self.failed))),
+      failures,
+    );
+
+    let help = "p pause/resume  +/- concurrency  up/down select  k kill task  d dump output  q stop/quit";
+    let footer_line = if self.status.is_empty() {
+      help.to_string()
+    } else {
+      format!("{}  |  {help}", self.status)
+    };
+    frame.render_widget(Paragraph::new(footer_line).dim(), footer);
+  }
+}
+
+/// Writes the output a running task printed so far, stderr lines marked as such.
+fn dump_output(task: &RunningTask, path: &Path) -> std::io::Result<()> {
+  let mut content = String::new();
+  for (stream, line) in &task.output {
+    if *stream == OutputStream::Stderr {
+      content.push_str("[stderr] ");
+    }
+    content.push_str(line);
+    content.push('\n');
+  }
+  std::fs::write(path, content)
+}
+
+/// Shows the dashboard until the run is over and the user quits.
+pub async fn run(
+  handle: PoolHandle,
+  mut events: mpsc::UnboundedReceiver<PoolEvent>,
+  total_tasks: Option<usize>,
+  recorder: &mut TaskRecorder,
+) -> std::io::Result<()> {
+  let (key_tx, mut keys) = mpsc::unbounded_channel();
+  // crossterm reads keys with blocking calls, poll so that the thread ends with the dashboard
+  std::thread::spawn(move || {
+    while !key_tx.is_closed() {
+      match event::poll(KEY_POLL_INTERVAL) {
+        Ok(true) => match event::read() {
+          Ok(Event::Key(key)) => {
+            let _ = key_tx.send(key);
+          }
+          Ok(_) => {}
+          Err(_) => break,
+        },
+        Ok(false) => {}
+        Err(_) => break,
+      }
+    }
+  });
+
+  let mut terminal = ratatui::init();
+  let mut dashboard = Dashboard::new(total_tasks);
+  let mut redraw = time::interval(REDRAW_INTERVAL);
+  let result = loop {
+    tokio::select! {
+      event = events.recv(), if !dashboard.finished => match event {
+        Some(event) => {
+          if let PoolEvent::TaskFinished { result, .. } = &event {
+            for warning in recorder.record(result).await {
+              dashboard.status = format!("Warning: {warning}");
+            }
+          }
+          dashboard.record(event);
+        }
+        None => dashboard.finished = true,
+      },
+      Some(key) = keys.recv() => {
+        if !dashboard.handle_key(key, &handle) {
+          break Ok(());
+        }
+      }
+      _ = redraw.tick() => {
+        if let Err(e) = terminal.draw(|frame| dashboard.render(frame, &handle)) {
+          break Err(e);
+        }
+      }
+    }
+  };
+  ratatui::restore();
+  result
+}